@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, str::FromStr};
+
+// environment variables are this plus the setting name in upper case, e.g. MOVIEDB_BASE_YEAR.
+pub const ENV_PREFIX: &str = "MOVIEDB_";
+
+// one layer of settings, from the config file, the environment or the command line. the same
+// names are used everywhere: `base_year` in the file, MOVIEDB_BASE_YEAR, `--base-year`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub inputs: Option<Vec<String>>,
+    // YYYY-MM; releases after it are skipped.
+    pub last_run: Option<String>,
+    // YYYY-MM; releases before it are skipped.
+    pub since: Option<String>,
+    pub credits: Option<String>,
+    pub keywords: Option<String>,
+    pub links: Option<String>,
+    pub ratings: Option<String>,
+    pub country: Option<String>,
+    pub cpi: Option<String>,
+    pub base_year: Option<i32>,
+    pub quality: Option<String>,
+    pub min_budget: Option<i64>,
+    pub min_revenue: Option<i64>,
+    pub min_ratio: Option<f64>,
+    pub max_ratio: Option<f64>,
+    pub max_z: Option<f64>,
+    pub rejects: Option<String>,
+    pub sqlite: Option<String>,
+    pub port: Option<u16>,
+    // metric the revenue-style rankings are sorted by, e.g. "roi" or "hit_rate".
+    pub rank_by: Option<String>,
+    // keep per-company sketches for percentiles and histograms; costs memory.
+    pub distributions: Option<bool>,
+    // co-production graph output; .graphml or .dot by extension.
+    pub graph: Option<String>,
+    // full, equal, lead or weighted: how company figures share a co-produced movie.
+    pub allocation: Option<String>,
+    // company_id,weight csv for the weighted allocation.
+    pub weights: Option<String>,
+    // months of revenue and releases to project per company.
+    pub forecast_periods: Option<usize>,
+    // trailing years held out to score the forecasts against.
+    pub holdout_years: Option<usize>,
+    // text or json; json prints only the yearly ranking, as one document.
+    pub format: Option<String>,
+    // what the yearly ranking groups movies by: country, company or genre.
+    pub group_by: Option<String>,
+}
+
+impl Settings {
+    pub fn from_file(path: &str) -> Result<Settings, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        toml::from_str(&contents).map_err(|e| format!("{path}: {e}"))
+    }
+
+    // every MOVIEDB_* variable except MOVIEDB_CONFIG, which picks the file instead. the
+    // environment is shared with everything else, so a name that isn't a setting is only warned
+    // about; a bad value for a real one is still an error.
+    pub fn from_env(vars: impl Iterator<Item = (String, String)>) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+        for (name, value) in vars {
+            match name.strip_prefix(ENV_PREFIX) {
+                Some("CONFIG") | None => {}
+                Some(key) => {
+                    if !settings.assign(&key.to_lowercase(), &value)? {
+                        eprintln!("warning: ignoring {name}, not a setting");
+                    }
+                }
+            }
+        }
+        Ok(settings)
+    }
+
+    // `key` is the setting name as written in the file; `inputs` takes a comma separated list.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if self.assign(key, value)? {
+            Ok(())
+        } else {
+            Err(format!("unknown setting {key}"))
+        }
+    }
+
+    // false when `key` isn't a setting.
+    fn assign(&mut self, key: &str, value: &str) -> Result<bool, String> {
+        let value = value.to_string();
+        match key {
+            "inputs" => self.inputs = Some(value.split(',').map(String::from).collect()),
+            "last_run" => self.last_run = Some(value),
+            "since" => self.since = Some(value),
+            "credits" => self.credits = Some(value),
+            "keywords" => self.keywords = Some(value),
+            "links" => self.links = Some(value),
+            "ratings" => self.ratings = Some(value),
+            "country" => self.country = Some(value),
+            "cpi" => self.cpi = Some(value),
+            "base_year" => self.base_year = Some(parse(key, &value)?),
+            "quality" => self.quality = Some(value),
+            "min_budget" => self.min_budget = Some(parse(key, &value)?),
+            "min_revenue" => self.min_revenue = Some(parse(key, &value)?),
+            "min_ratio" => self.min_ratio = Some(parse(key, &value)?),
+            "max_ratio" => self.max_ratio = Some(parse(key, &value)?),
+            "max_z" => self.max_z = Some(parse(key, &value)?),
+            "rejects" => self.rejects = Some(value),
+            "sqlite" => self.sqlite = Some(value),
+            "port" => self.port = Some(parse(key, &value)?),
+            "rank_by" => self.rank_by = Some(value),
+            "distributions" => self.distributions = Some(parse(key, &value)?),
+            "graph" => self.graph = Some(value),
+            "allocation" => self.allocation = Some(value),
+            "weights" => self.weights = Some(value),
+            "forecast_periods" => self.forecast_periods = Some(parse(key, &value)?),
+            "holdout_years" => self.holdout_years = Some(parse(key, &value)?),
+            "format" => self.format = Some(value),
+            "group_by" => self.group_by = Some(value),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // anything set in `over` wins.
+    pub fn merge(self, over: Settings) -> Settings {
+        Settings {
+            inputs: over.inputs.or(self.inputs),
+            last_run: over.last_run.or(self.last_run),
+            since: over.since.or(self.since),
+            credits: over.credits.or(self.credits),
+            keywords: over.keywords.or(self.keywords),
+            links: over.links.or(self.links),
+            ratings: over.ratings.or(self.ratings),
+            country: over.country.or(self.country),
+            cpi: over.cpi.or(self.cpi),
+            base_year: over.base_year.or(self.base_year),
+            quality: over.quality.or(self.quality),
+            min_budget: over.min_budget.or(self.min_budget),
+            min_revenue: over.min_revenue.or(self.min_revenue),
+            min_ratio: over.min_ratio.or(self.min_ratio),
+            max_ratio: over.max_ratio.or(self.max_ratio),
+            max_z: over.max_z.or(self.max_z),
+            rejects: over.rejects.or(self.rejects),
+            sqlite: over.sqlite.or(self.sqlite),
+            port: over.port.or(self.port),
+            rank_by: over.rank_by.or(self.rank_by),
+            distributions: over.distributions.or(self.distributions),
+            graph: over.graph.or(self.graph),
+            allocation: over.allocation.or(self.allocation),
+            weights: over.weights.or(self.weights),
+            forecast_periods: over.forecast_periods.or(self.forecast_periods),
+            holdout_years: over.holdout_years.or(self.holdout_years),
+            format: over.format.or(self.format),
+            group_by: over.group_by.or(self.group_by),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("settings always serialize")
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid {key}: {value}"))
+}
+
+mod tests;
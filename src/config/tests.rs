@@ -0,0 +1,62 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn file_env_and_flags_layer() {
+        let file: Settings = toml::from_str(
+            r#"
+            inputs = ["shards/*.csv.gz"]
+            country = "GB"
+            base_year = 2000
+            quality = "report"
+            "#,
+        )
+        .unwrap();
+        let env = Settings::from_env(
+            [
+                ("MOVIEDB_BASE_YEAR", "2010"),
+                ("MOVIEDB_CONFIG", "ignored.toml"),
+                // not ours, e.g. another tool's; warned about and skipped.
+                ("MOVIEDB_DEBUG_LOG", "1"),
+                ("HOME", "/root"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .into_iter(),
+        )
+        .unwrap();
+        let mut cli = Settings::default();
+        cli.set("quality", "exclude").unwrap();
+
+        let merged = file.merge(env).merge(cli);
+        assert_eq!(merged.inputs, Some(vec!["shards/*.csv.gz".to_string()]));
+        assert_eq!(merged.country.as_deref(), Some("GB"));
+        assert_eq!(merged.base_year, Some(2010));
+        assert_eq!(merged.quality.as_deref(), Some("exclude"));
+
+        // what `config print` shows reads back as the same settings.
+        assert_eq!(
+            toml::from_str::<Settings>(&merged.to_toml()).unwrap(),
+            merged
+        );
+    }
+
+    #[test]
+    fn bad_values() {
+        let mut settings = Settings::default();
+        assert_eq!(
+            settings.set("port", "http"),
+            Err("invalid port: http".to_string())
+        );
+        assert_eq!(
+            settings.set("colour", "red"),
+            Err("unknown setting colour".to_string())
+        );
+        assert!(toml::from_str::<Settings>("colour = \"red\"").is_err());
+        assert!(
+            Settings::from_env([("MOVIEDB_PORT".to_string(), "x".to_string())].into_iter())
+                .is_err()
+        );
+    }
+}
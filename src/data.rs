@@ -1,30 +1,283 @@
 use chrono::NaiveDate;
+use csv::StringRecord;
 use kstring::KString;
-use std::{collections::HashSet, fs::File, io::BufReader};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::{BufReader, Read},
+};
 
-use crate::{parsing, Config};
+use crate::{
+    parsing::{self, CreditsRowRaw, KeywordsRowRaw, LinkRowRaw, RatingRowRaw, SchemaError},
+    quality::{Flag, Mode},
+    query::sketch::Sketch,
+    Config,
+};
 
 // intermediate data structures after parsing and filtering data.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Movie {
     pub id: KString,
     pub genres: HashSet<i64>,
     pub production_companies: HashSet<i64>,
+    // first listed production company; the set above loses the order.
+    pub lead_company: Option<i64>,
     pub release_date: NaiveDate,
     pub budget: i64,
     pub revenue: i64,
     pub profit: i64,
     pub avg_populatarity: f32,
     pub status: Status,
+    pub directors: HashSet<i64>,
+    // billing order.
+    pub cast: Vec<i64>,
+    pub keywords: HashSet<i64>,
+    pub ratings: RatingSummary,
+    pub title: KString,
+    pub imdb_id: Option<KString>,
+    // minutes.
+    pub runtime: Option<f32>,
+    pub original_language: Option<KString>,
+    // iso 639-1 codes.
+    pub spoken_languages: HashSet<KString>,
+    // iso 3166-1 codes.
+    pub production_countries: HashSet<KString>,
+    pub collection: Option<Collection>,
+    pub vote_average: Option<f32>,
+    pub vote_count: Option<u32>,
+    pub adult: Option<bool>,
+    // constant dollars, only filled in by the inflation stage.
+    pub adjusted: Option<Adjusted>,
+    // input file the movie was read from, when it came from a csv.
+    pub source: Option<KString>,
 }
 
-#[derive(PartialEq, Debug, Hash, Eq)]
+// inflation-adjusted counterparts of budget, revenue and profit.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Adjusted {
+    pub budget: i64,
+    pub revenue: i64,
+    pub profit: i64,
+}
+
+impl Adjusted {
+    // for summing aggregates: missing on one side counts as nothing to add.
+    pub fn combine(a: &Option<Adjusted>, b: &Option<Adjusted>) -> Option<Adjusted> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(Adjusted {
+                budget: a.budget + b.budget,
+                revenue: a.revenue + b.revenue,
+                profit: a.profit + b.profit,
+            }),
+            (Some(x), None) | (None, Some(x)) => Some(*x),
+            (None, None) => None,
+        }
+    }
+}
+
+// the franchise a movie belongs to, from belongs_to_collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collection {
+    pub id: i64,
+    pub name: KString,
+}
+
+// user ratings are 0.5 to 5.0 in half star steps.
+pub const RATING_BUCKETS: usize = 10;
+
+// mergeable so it can ride along in the aggregates; an empty summary means no ratings.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RatingSummary {
+    pub count: u64,
+    pub sum: f64,
+    // distribution[0] is 0.5 stars, distribution[9] is 5 stars.
+    pub distribution: [u64; RATING_BUCKETS],
+}
+
+impl RatingSummary {
+    pub fn add(&mut self, rating: f32) {
+        let bucket = ((rating * 2.0).round() as usize).clamp(1, RATING_BUCKETS) - 1;
+        self.count += 1;
+        self.sum += rating as f64;
+        self.distribution[bucket] += 1;
+    }
+
+    pub fn merge(&self, other: &RatingSummary) -> RatingSummary {
+        let mut distribution = self.distribution;
+        distribution
+            .iter_mut()
+            .zip(other.distribution)
+            .for_each(|(x, y)| *x += y);
+
+        RatingSummary {
+            count: self.count + other.count,
+            sum: self.sum + other.sum,
+            distribution,
+        }
+    }
+
+    // mean over every individual rating, so movies with more ratings weigh more.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+// revenue above this multiple of the budget makes a hit.
+pub const HIT_MULTIPLE: f64 = 2.5;
+
+// what ROI, hit rate and median profit are derived from, kept as components so they still add
+// up when aggregates are summed; the median comes from a sketch for the same reason. a budget of
+// 0 means unknown, so only movies with a known budget are counted at all.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Returns {
+    pub budgeted: u64,
+    pub budget: i64,
+    pub profit: i64,
+    pub hits: u64,
+    pub flops: u64,
+    // one per budgeted movie.
+    pub profits: Sketch,
+}
+
+impl Returns {
+    pub fn of(movie: &Movie) -> Returns {
+        if movie.budget <= 0 {
+            return Returns::default();
+        }
+
+        Returns {
+            budgeted: 1,
+            budget: movie.budget,
+            profit: movie.profit,
+            hits: (movie.revenue as f64 > movie.budget as f64 * HIT_MULTIPLE) as u64,
+            flops: (movie.revenue < movie.budget) as u64,
+            profits: Sketch::of(movie.profit as f64),
+        }
+    }
+
+    pub fn merge(&self, other: &Returns) -> Returns {
+        Returns {
+            budgeted: self.budgeted + other.budgeted,
+            budget: self.budget + other.budget,
+            profit: self.profit + other.profit,
+            hits: self.hits + other.hits,
+            flops: self.flops + other.flops,
+            profits: self.profits.merge(&other.profits),
+        }
+    }
+
+    // profit / budget.
+    pub fn roi(&self) -> Option<f64> {
+        (self.budget > 0).then(|| self.profit as f64 / self.budget as f64)
+    }
+
+    pub fn hit_rate(&self) -> Option<f64> {
+        (self.budgeted > 0).then(|| self.hits as f64 / self.budgeted as f64)
+    }
+
+    // within the sketch's 1% for anything but the smallest and largest profit.
+    pub fn median_profit(&self) -> Option<f64> {
+        self.profits.median()
+    }
+}
+
+// iso 3166-1 alpha-2 codes packed into an i64 so countries can share the ById machinery.
+pub fn country_id(code: &str) -> i64 {
+    code.bytes()
+        .take(2)
+        .fold(0, |acc, b| (acc << 8) | b.to_ascii_uppercase() as i64)
+}
+
+pub fn country_code(id: i64) -> String {
+    [(id >> 8) as u8, id as u8]
+        .iter()
+        .filter(|b| **b != 0)
+        .map(|b| *b as char)
+        .collect()
+}
+
+// id -> display name, e.g. genre 28 -> "Action".
+pub type Dictionary = BTreeMap<i64, KString>;
+
+// everything pulled out of a single read of the metadata file.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    pub movies: Vec<Movie>,
+    pub genres: Dictionary,
+    pub companies: Dictionary,
+    // cast and crew, only filled in when credits are read.
+    pub people: Dictionary,
+    // only filled in when keywords are read.
+    pub keywords: Dictionary,
+    // keyed by country_id.
+    pub countries: Dictionary,
+    // rows that didn't make it into `movies` (or were flagged on the way in), and why.
+    pub rejects: Vec<Reject>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Filtered {
+    NoRevenue,
+    AfterLastRun,
+    NotReleased,
+    BeforeSince,
+}
+
+impl Filtered {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NoRevenue => "no_revenue",
+            Self::AfterLastRun => "after_last_run",
+            Self::NotReleased => "not_released",
+            Self::BeforeSince => "before_since",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RejectReason {
+    // the row didn't deserialize into a MovieRowRaw.
+    Invalid(String),
+    Filtered(Filtered),
+    // failed data quality checks; `action` is what happened to the movie.
+    Quality { flags: Vec<Flag>, action: Mode },
+    // the same movie id came up again later; holds where the row that replaced it came from.
+    Duplicate(KString),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reject {
+    pub movie_id: KString,
+    // input file the row came from.
+    pub source: Option<KString>,
+    // line in the input file, when the reject came from reading it.
+    pub line: Option<u64>,
+    pub reason: RejectReason,
+}
+
+impl RejectReason {
+    // (kind, detail) as written to the rejects report.
+    pub fn describe(&self) -> (String, String) {
+        match self {
+            Self::Invalid(e) => ("invalid".to_string(), e.clone()),
+            Self::Filtered(filtered) => ("filtered".to_string(), filtered.name().to_string()),
+            Self::Quality { flags, action } => (
+                format!("quality_{}", action.outcome()),
+                flags.iter().map(Flag::name).collect::<Vec<_>>().join(";"),
+            ),
+            Self::Duplicate(source) => ("duplicate".to_string(), format!("superseded by {source}")),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Default, Hash, Eq)]
 pub enum Status {
     Released,
+    #[default]
     Other,
 }
 
 impl Status {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(enum_str: &str) -> Status {
         if enum_str.is_empty() {
             Self::Other
@@ -37,21 +290,223 @@ impl Status {
     }
 }
 
-pub fn read_movie_metadata(file: &File, config: &Config) -> Vec<Movie> {
-    let reader = BufReader::new(file);
-    let mut reader = csv::ReaderBuilder::new()
+// shared by ingest and `stats` so both see the file the same way.
+pub fn movie_reader<R: Read>(reader: R) -> csv::Reader<BufReader<R>> {
+    let reader = BufReader::new(reader);
+    csv::ReaderBuilder::new()
         // .has_headers(true)
         // .trim(Trim::All)
-        .from_reader(reader);
+        .from_reader(reader)
+}
 
-    let headers = reader.headers().unwrap().clone();
+// reads every (source, reader) input into one catalog. each input must pass the schema check
+// and carry the same columns as the first, and a movie id seen again replaces the earlier row,
+// or drops it if the later row is rejected itself.
+pub fn read_movie_metadata<R: Read>(
+    inputs: impl IntoIterator<Item = (String, R)>,
+    config: &Config,
+) -> Result<Catalog, (String, SchemaError)> {
+    let mut catalog = Catalog::default();
+    let mut columns: Option<BTreeSet<String>> = None;
+    // positions in `movies`; a superseded movie leaves a None behind so the rest keep theirs.
+    let mut movies: Vec<Option<Movie>> = vec![];
+    let mut seen: HashMap<KString, usize> = HashMap::new();
 
-    reader
-        .records()
-        .flat_map(|x| x)
-        .flat_map(|s| parsing::from_record(&s, &headers))
-        .flat_map(|x| x.to_movie(&config.last_run))
+    for (source, reader) in inputs {
+        let mut reader = movie_reader(reader);
+        let headers = match parsing::check_schema(&read_headers(&mut reader)) {
+            Ok(schema) => schema.headers,
+            Err(e) => return Err((source, e)),
+        };
+
+        let used = parsing::used_columns(&headers);
+        match &columns {
+            None => columns = Some(used),
+            Some(first) if *first != used => {
+                return Err((
+                    source,
+                    SchemaError {
+                        missing: first.difference(&used).cloned().collect(),
+                        unknown: used.difference(first).cloned().collect(),
+                    },
+                ))
+            }
+            Some(_) => {}
+        }
+
+        let source = KString::from_string(source);
+        let id_column = headers.iter().position(|x| x == "id");
+
+        reader.records().flatten().for_each(|record| {
+            let line = record.position().map(|x| x.line());
+            let reject = |movie_id: KString, reason: RejectReason| Reject {
+                movie_id,
+                source: Some(source.clone()),
+                line,
+                reason,
+            };
+
+            let outcome = match parsing::from_record(&record, &headers) {
+                Ok(raw) => match raw.to_movie(&config.last_run) {
+                    Ok(movie) if config.since.is_some_and(|x| movie.release_date < x) => Err(
+                        reject(raw.id(), RejectReason::Filtered(Filtered::BeforeSince)),
+                    ),
+                    Ok(mut movie) => {
+                        catalog.genres.extend(raw.genre_names());
+                        catalog.companies.extend(raw.company_names());
+                        catalog.countries.extend(raw.country_names());
+                        movie.source = Some(source.clone());
+                        Ok(movie)
+                    }
+                    Err(filtered) => Err(reject(raw.id(), RejectReason::Filtered(filtered))),
+                },
+                Err(e) => Err(reject(
+                    id_column
+                        .and_then(|i| record.get(i))
+                        .map(KString::from_ref)
+                        .unwrap_or_default(),
+                    RejectReason::Invalid(e.to_string()),
+                )),
+            };
+
+            let id = match &outcome {
+                Ok(movie) => &movie.id,
+                Err(reject) => &reject.movie_id,
+            };
+            // the later row wins whatever happened to it.
+            let previous = seen.remove(id);
+            if let Some(replaced) = previous.and_then(|i| movies[i].take()) {
+                catalog.rejects.push(Reject {
+                    movie_id: replaced.id,
+                    source: replaced.source,
+                    line: None,
+                    reason: RejectReason::Duplicate(source.clone()),
+                });
+            }
+            match outcome {
+                // a replacement keeps the earlier row's place.
+                Ok(movie) => match previous {
+                    Some(i) => {
+                        seen.insert(movie.id.clone(), i);
+                        movies[i] = Some(movie);
+                    }
+                    None => {
+                        seen.insert(movie.id.clone(), movies.len());
+                        movies.push(Some(movie));
+                    }
+                },
+                Err(reject) => catalog.rejects.push(reject),
+            }
+        });
+    }
+
+    catalog.movies = movies.into_iter().flatten().collect();
+    Ok(catalog)
+}
+
+// an unreadable or empty file has no columns, which check_schema then reports as missing.
+pub fn read_headers<R: Read>(reader: &mut csv::Reader<R>) -> StringRecord {
+    reader.headers().cloned().unwrap_or_default()
+}
+
+// movie id -> position in catalog.movies, for joining the companion files.
+fn index_movies(catalog: &Catalog) -> HashMap<KString, usize> {
+    catalog
+        .movies
+        .iter()
+        .enumerate()
+        .map(|(i, movie)| (movie.id.clone(), i))
         .collect()
 }
 
+// joins credits.csv onto the movies already read, by movie id.
+pub fn read_credits(reader: impl Read, catalog: &mut Catalog) {
+    let mut reader = csv::ReaderBuilder::new().from_reader(BufReader::new(reader));
+    let index = index_movies(catalog);
+
+    reader
+        .deserialize::<CreditsRowRaw>()
+        .flatten()
+        .for_each(|row| {
+            if let Some(movie) = index.get(&row.id()).map(|i| &mut catalog.movies[*i]) {
+                let directors = row.crew_with_job("Director");
+                let cast = row.cast();
+
+                movie.directors = directors.iter().map(|x| x.id).collect();
+                movie.cast = cast.iter().map(|x| x.id).collect();
+                catalog
+                    .people
+                    .extend(directors.into_iter().chain(cast).map(|x| (x.id, x.name)));
+            }
+        });
+}
+
+// joins keywords.csv onto the movies already read, by movie id.
+pub fn read_keywords(reader: impl Read, catalog: &mut Catalog) {
+    let mut reader = csv::ReaderBuilder::new().from_reader(BufReader::new(reader));
+    let index = index_movies(catalog);
+
+    reader
+        .deserialize::<KeywordsRowRaw>()
+        .flatten()
+        .for_each(|row| {
+            if let Some(movie) = index.get(&row.id()).map(|i| &mut catalog.movies[*i]) {
+                movie.keywords = row.keyword_ids();
+                catalog.keywords.extend(row.keyword_names());
+            }
+        });
+}
+
+// ratings.csv is keyed by MovieLens ids, so links.csv maps them to the tmdb ids we use.
+// ratings.csv is tens of millions of rows; it's streamed through a single reused record.
+// malformed rows are skipped and counted; only a failing read stops it.
+pub fn read_ratings(
+    links: impl Read,
+    ratings: impl Read,
+    catalog: &mut Catalog,
+) -> Result<usize, csv::Error> {
+    let index = index_movies(catalog);
+
+    let mut links_reader = csv::ReaderBuilder::new().from_reader(BufReader::new(links));
+    let movielens_to_movie: HashMap<i64, usize> = links_reader
+        .deserialize::<LinkRowRaw>()
+        .flatten()
+        .flat_map(|row| Some((row.movie_id(), *index.get(&row.tmdb_id()?)?)))
+        .collect();
+
+    let mut reader = csv::ReaderBuilder::new().from_reader(BufReader::new(ratings));
+    let headers = match reader.byte_headers() {
+        Ok(headers) => headers.clone(),
+        Err(_) => return Ok(0),
+    };
+
+    let mut summaries: HashMap<usize, RatingSummary> = HashMap::new();
+    let mut record = csv::ByteRecord::new();
+    let mut skipped = 0;
+    loop {
+        match reader.read_byte_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) if matches!(e.kind(), csv::ErrorKind::Io(_)) => return Err(e),
+            // e.g. a short row; the reader has moved past it.
+            Err(_) => {
+                skipped += 1;
+                continue;
+            }
+        }
+        let Ok(row) = record.deserialize::<RatingRowRaw>(Some(&headers)) else {
+            skipped += 1;
+            continue;
+        };
+        if let Some(i) = movielens_to_movie.get(&row.movie_id()) {
+            summaries.entry(*i).or_default().add(row.rating());
+        }
+    }
+
+    for (i, summary) in summaries {
+        catalog.movies[i].ratings = summary;
+    }
+    Ok(skipped)
+}
+
 mod tests;
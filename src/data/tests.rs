@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use super::super::*;
 
@@ -10,4 +11,119 @@ mod tests {
         assert_eq!(Status::from_str("Released"), Status::Released);
         assert_eq!(Status::from_str("RelEAsed"), Status::Released);
     }
+
+    #[test]
+    fn rating_summary() {
+        let mut a = RatingSummary::default();
+        assert_eq!(a.mean(), None);
+        a.add(5.0);
+        a.add(0.5);
+        let mut b = RatingSummary::default();
+        b.add(3.5);
+
+        let merged = a.merge(&b);
+        assert_eq!(merged.count, 3);
+        assert_eq!(merged.mean(), Some(3.0));
+        assert_eq!(merged.distribution[0], 1);
+        assert_eq!(merged.distribution[6], 1);
+        assert_eq!(merged.distribution[9], 1);
+    }
+
+    #[test]
+    fn ratings_join_through_links() {
+        let mut catalog = Catalog {
+            movies: vec![
+                Movie {
+                    id: KString::from_static("862"),
+                    ..Default::default()
+                },
+                Movie {
+                    id: KString::from_static("8844"),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let links = "movieId,imdbId,tmdbId\n1,0114709,862\n2,0113497,8844\n3,0113228,\n";
+        // a short row and an unparseable rating don't end the stream.
+        let ratings =
+            "userId,movieId,rating,timestamp\n1,1,4.0,964982703\n3,1\n4,1,abc,964981247\n2,1,5.0,964981247\n1,2,3.0,964982224\n1,3,1.0,964982224\n1,99,2.0,964982224\n";
+
+        assert_eq!(
+            read_ratings(links.as_bytes(), ratings.as_bytes(), &mut catalog).unwrap(),
+            2
+        );
+
+        assert_eq!(catalog.movies[0].ratings.count, 2);
+        assert_eq!(catalog.movies[0].ratings.mean(), Some(4.5));
+        assert_eq!(catalog.movies[1].ratings.count, 1);
+        assert_eq!(catalog.movies[1].ratings.distribution[5], 1);
+    }
+
+    #[test]
+    fn country_ids_round_trip() {
+        assert_eq!(country_code(country_id("US")), "US");
+        assert_eq!(country_id("us"), country_id("US"));
+        assert_ne!(country_id("US"), country_id("GB"));
+        assert_eq!(country_code(country_id("")), "");
+    }
+
+    #[test]
+    fn shards_merge_and_dedupe() {
+        let config = crate::parse_args(
+            &mut ["moviedb", "shard.csv"].map(String::from).into_iter(),
+            std::iter::empty(),
+        );
+        let header =
+            "id,genres,production_companies,release_date,budget,revenue,popularity,status\n";
+        let january = format!(
+            "{header}1,[],[],1995-10-30,100,200,1.5,Released\n2,[],[],1995-10-30,100,0,1.5,Released\n"
+        );
+        let february = format!("{header}1,[],[],1995-10-30,100,300,1.5,Released\n");
+        let inputs = [("jan.csv", &january), ("feb.csv", &february)]
+            .map(|(source, contents)| (source.to_string(), contents.as_bytes()));
+
+        let catalog = read_movie_metadata(inputs, &config).unwrap();
+        assert_eq!(catalog.movies.len(), 1);
+        assert_eq!(catalog.movies[0].revenue, 300);
+        assert_eq!(catalog.movies[0].source.as_deref(), Some("feb.csv"));
+        assert_eq!(
+            catalog
+                .rejects
+                .iter()
+                .map(|x| x.reason.describe().0)
+                .collect::<Vec<_>>(),
+            vec!["filtered", "duplicate"]
+        );
+        assert_eq!(catalog.rejects[0].source.as_deref(), Some("jan.csv"));
+        assert_eq!(catalog.rejects[0].line, Some(3));
+        assert_eq!(catalog.rejects[1].source.as_deref(), Some("jan.csv"));
+
+        // a later row that is filtered out still supersedes the earlier one.
+        let march = format!("{header}1,[],[],1995-10-30,100,0,1.5,Released\n");
+        let inputs = [("jan.csv", &january), ("mar.csv", &march)]
+            .map(|(source, contents)| (source.to_string(), contents.as_bytes()));
+        let catalog = read_movie_metadata(inputs, &config).unwrap();
+        assert!(catalog.movies.is_empty());
+        assert_eq!(
+            catalog
+                .rejects
+                .iter()
+                .map(|x| (x.reason.describe().0, x.source.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("filtered".to_string(), Some("jan.csv")),
+                ("duplicate".to_string(), Some("jan.csv")),
+                ("filtered".to_string(), Some("mar.csv")),
+            ]
+        );
+
+        // a shard with a different set of columns is refused.
+        let titled = header.replace("status\n", "status,title\n");
+        let inputs = [("jan.csv", &january), ("titled.csv", &titled)]
+            .map(|(source, contents)| (source.to_string(), contents.as_bytes()));
+        let (source, e) = read_movie_metadata(inputs, &config).unwrap_err();
+        assert_eq!(source, "titled.csv");
+        assert_eq!(e.unknown, vec!["title".to_string()]);
+    }
 }
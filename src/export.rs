@@ -0,0 +1,516 @@
+use chrono::NaiveDate;
+use json::{array, JsonValue};
+use kstring::KString;
+use rusqlite::{
+    params,
+    types::{FromSql, Type},
+    Connection, Transaction,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::{
+    data::{
+        country_code, country_id, Adjusted, Catalog, Collection, Dictionary, Movie, RatingSummary,
+        Status, RATING_BUCKETS,
+    },
+    query::{allocation::Allocation, bucket_movies, by_production_companies::movie_to_details},
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS movies (
+    id TEXT PRIMARY KEY,
+    release_date TEXT NOT NULL,
+    budget INTEGER NOT NULL,
+    revenue INTEGER NOT NULL,
+    profit INTEGER NOT NULL,
+    popularity REAL NOT NULL,
+    status TEXT NOT NULL,
+    rating_count INTEGER NOT NULL DEFAULT 0,
+    rating_mean REAL,
+    title TEXT NOT NULL DEFAULT '',
+    imdb_id TEXT,
+    runtime REAL,
+    original_language TEXT,
+    collection_id INTEGER REFERENCES collections(id),
+    vote_average REAL,
+    vote_count INTEGER,
+    adult INTEGER,
+    adjusted_budget INTEGER,
+    adjusted_revenue INTEGER,
+    adjusted_profit INTEGER,
+    lead_company INTEGER,
+    -- json list of rating counts, 0.5 stars first.
+    rating_distribution TEXT
+);
+CREATE TABLE IF NOT EXISTS collections (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS genres (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS companies (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL
+);
+-- keyed by iso 3166-1 code, e.g. US.
+CREATE TABLE IF NOT EXISTS countries (
+    code TEXT PRIMARY KEY,
+    name TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS movie_genres (
+    movie_id TEXT NOT NULL REFERENCES movies(id),
+    genre_id INTEGER NOT NULL REFERENCES genres(id),
+    PRIMARY KEY (movie_id, genre_id)
+);
+CREATE TABLE IF NOT EXISTS movie_companies (
+    movie_id TEXT NOT NULL REFERENCES movies(id),
+    company_id INTEGER NOT NULL REFERENCES companies(id),
+    PRIMARY KEY (movie_id, company_id)
+);
+CREATE TABLE IF NOT EXISTS movie_countries (
+    movie_id TEXT NOT NULL REFERENCES movies(id),
+    country_code TEXT NOT NULL REFERENCES countries(code),
+    PRIMARY KEY (movie_id, country_code)
+);
+-- iso 639-1 codes, e.g. en.
+CREATE TABLE IF NOT EXISTS movie_languages (
+    movie_id TEXT NOT NULL REFERENCES movies(id),
+    language TEXT NOT NULL,
+    PRIMARY KEY (movie_id, language)
+);
+CREATE TABLE IF NOT EXISTS company_monthly (
+    company_id INTEGER NOT NULL REFERENCES companies(id),
+    year INTEGER NOT NULL,
+    month INTEGER NOT NULL,
+    movie_count INTEGER NOT NULL,
+    budget INTEGER NOT NULL,
+    profit INTEGER NOT NULL,
+    revenue INTEGER NOT NULL,
+    avg_popularity REAL NOT NULL,
+    rating_count INTEGER NOT NULL DEFAULT 0,
+    avg_rating REAL,
+    adjusted_budget INTEGER,
+    adjusted_profit INTEGER,
+    adjusted_revenue INTEGER,
+    roi REAL,
+    median_profit REAL,
+    hit_rate REAL,
+    flops INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (company_id, year, month)
+);
+CREATE TABLE IF NOT EXISTS company_monthly_distributions (
+    company_id INTEGER NOT NULL REFERENCES companies(id),
+    year INTEGER NOT NULL,
+    month INTEGER NOT NULL,
+    metric TEXT NOT NULL,
+    p50 REAL,
+    p90 REAL,
+    p99 REAL,
+    -- json list of [lower, upper, count], one per power of ten.
+    histogram TEXT NOT NULL,
+    PRIMARY KEY (company_id, year, month, metric)
+);
+";
+
+pub fn write_sqlite(
+    path: &str,
+    catalog: &Catalog,
+    allocation: &Allocation,
+    distributions: bool,
+) -> rusqlite::Result<()> {
+    let mut conn = Connection::open(path)?;
+    write_catalog(&mut conn, catalog, allocation, distributions)
+}
+
+// everything is an upsert, so re-running over the same (or a newer) export leaves one row per key.
+pub fn write_catalog(
+    conn: &mut Connection,
+    catalog: &Catalog,
+    allocation: &Allocation,
+    distributions: bool,
+) -> rusqlite::Result<()> {
+    conn.execute_batch(SCHEMA)?;
+
+    let collections: Dictionary = catalog
+        .movies
+        .iter()
+        .flat_map(|movie| movie.collection.as_ref())
+        .map(|collection| (collection.id, collection.name.clone()))
+        .collect();
+
+    let tx = conn.transaction()?;
+    upsert_dictionary(&tx, "genres", &catalog.genres)?;
+    upsert_dictionary(&tx, "companies", &catalog.companies)?;
+    upsert_dictionary(&tx, "collections", &collections)?;
+    upsert_countries(&tx, &catalog.countries)?;
+    upsert_movies(&tx, catalog)?;
+    write_company_monthly(&tx, allocation, distributions)?;
+    tx.commit()
+}
+
+fn upsert_dictionary(
+    tx: &Transaction,
+    table: &str,
+    dictionary: &Dictionary,
+) -> rusqlite::Result<()> {
+    let mut stmt = tx.prepare(&format!(
+        "INSERT INTO {table} (id, name) VALUES (?1, ?2)
+         ON CONFLICT(id) DO UPDATE SET name = excluded.name"
+    ))?;
+
+    for (id, name) in dictionary {
+        stmt.execute(params![id, name.as_str()])?;
+    }
+
+    Ok(())
+}
+
+fn upsert_countries(tx: &Transaction, countries: &Dictionary) -> rusqlite::Result<()> {
+    let mut stmt = tx.prepare(
+        "INSERT INTO countries (code, name) VALUES (?1, ?2)
+         ON CONFLICT(code) DO UPDATE SET name = excluded.name",
+    )?;
+
+    for (id, name) in countries {
+        stmt.execute(params![country_code(*id), name.as_str()])?;
+    }
+
+    Ok(())
+}
+
+fn upsert_movies(tx: &Transaction, catalog: &Catalog) -> rusqlite::Result<()> {
+    let mut movie_stmt = tx.prepare(
+        "INSERT INTO movies
+            (id, release_date, budget, revenue, profit, popularity, status, rating_count, rating_mean,
+             title, imdb_id, runtime, original_language, collection_id, vote_average, vote_count,
+             adult, adjusted_budget, adjusted_revenue, adjusted_profit, lead_company,
+             rating_distribution)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+                 ?19, ?20, ?21, ?22)
+         ON CONFLICT(id) DO UPDATE SET
+            release_date = excluded.release_date,
+            budget = excluded.budget,
+            revenue = excluded.revenue,
+            profit = excluded.profit,
+            popularity = excluded.popularity,
+            status = excluded.status,
+            rating_count = excluded.rating_count,
+            rating_mean = excluded.rating_mean,
+            title = excluded.title,
+            imdb_id = excluded.imdb_id,
+            runtime = excluded.runtime,
+            original_language = excluded.original_language,
+            collection_id = excluded.collection_id,
+            vote_average = excluded.vote_average,
+            vote_count = excluded.vote_count,
+            adult = excluded.adult,
+            adjusted_budget = excluded.adjusted_budget,
+            adjusted_revenue = excluded.adjusted_revenue,
+            adjusted_profit = excluded.adjusted_profit,
+            lead_company = excluded.lead_company,
+            rating_distribution = excluded.rating_distribution",
+    )?;
+    // links are replaced wholesale so a movie that lost a genre between runs doesn't keep it.
+    let mut clear_genres = tx.prepare("DELETE FROM movie_genres WHERE movie_id = ?1")?;
+    let mut clear_companies = tx.prepare("DELETE FROM movie_companies WHERE movie_id = ?1")?;
+    let mut clear_countries = tx.prepare("DELETE FROM movie_countries WHERE movie_id = ?1")?;
+    let mut clear_languages = tx.prepare("DELETE FROM movie_languages WHERE movie_id = ?1")?;
+    let mut genre_stmt =
+        tx.prepare("INSERT INTO movie_genres (movie_id, genre_id) VALUES (?1, ?2)")?;
+    let mut company_stmt =
+        tx.prepare("INSERT INTO movie_companies (movie_id, company_id) VALUES (?1, ?2)")?;
+    let mut country_stmt =
+        tx.prepare("INSERT INTO movie_countries (movie_id, country_code) VALUES (?1, ?2)")?;
+    let mut language_stmt =
+        tx.prepare("INSERT INTO movie_languages (movie_id, language) VALUES (?1, ?2)")?;
+
+    for movie in &catalog.movies {
+        let id = movie.id.as_str();
+        movie_stmt.execute(params![
+            id,
+            movie.release_date.to_string(),
+            movie.budget,
+            movie.revenue,
+            movie.profit,
+            movie.avg_populatarity,
+            format!("{:?}", movie.status),
+            movie.ratings.count,
+            movie.ratings.mean(),
+            movie.title.as_str(),
+            movie.imdb_id.as_deref(),
+            movie.runtime,
+            movie.original_language.as_deref(),
+            movie.collection.as_ref().map(|x| x.id),
+            movie.vote_average,
+            movie.vote_count,
+            movie.adult,
+            movie.adjusted.map(|x| x.budget),
+            movie.adjusted.map(|x| x.revenue),
+            movie.adjusted.map(|x| x.profit),
+            movie.lead_company,
+            JsonValue::from(movie.ratings.distribution.to_vec()).dump(),
+        ])?;
+
+        clear_genres.execute([id])?;
+        for genre in &movie.genres {
+            genre_stmt.execute(params![id, genre])?;
+        }
+
+        clear_companies.execute([id])?;
+        for company in &movie.production_companies {
+            company_stmt.execute(params![id, company])?;
+        }
+
+        clear_countries.execute([id])?;
+        for country in &movie.production_countries {
+            country_stmt.execute(params![id, country.as_str()])?;
+        }
+
+        clear_languages.execute([id])?;
+        for language in &movie.spoken_languages {
+            language_stmt.execute(params![id, language.as_str()])?;
+        }
+    }
+
+    Ok(())
+}
+
+// rebuilt from every movie in the database rather than just this run's, so after exporting
+// several shards the aggregates still agree with `movies`.
+fn write_company_monthly(
+    tx: &Transaction,
+    allocation: &Allocation,
+    with_distributions: bool,
+) -> rusqlite::Result<()> {
+    let movies = read_catalog(tx)?.movies;
+    let by_company = bucket_movies(&movies, |x| {
+        movie_to_details(x, allocation, with_distributions)
+    });
+
+    tx.execute_batch(
+        "DELETE FROM company_monthly_distributions;
+         DELETE FROM company_monthly;",
+    )?;
+    let mut distributions = tx.prepare(
+        "INSERT INTO company_monthly_distributions
+            (company_id, year, month, metric, p50, p90, p99, histogram)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(company_id, year, month, metric) DO UPDATE SET
+            p50 = excluded.p50,
+            p90 = excluded.p90,
+            p99 = excluded.p99,
+            histogram = excluded.histogram",
+    )?;
+    let mut stmt = tx.prepare(
+        "INSERT INTO company_monthly
+            (company_id, year, month, movie_count, budget, profit, revenue, avg_popularity,
+             rating_count, avg_rating, adjusted_budget, adjusted_profit, adjusted_revenue,
+             roi, median_profit, hit_rate, flops)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+         ON CONFLICT(company_id, year, month) DO UPDATE SET
+            movie_count = excluded.movie_count,
+            budget = excluded.budget,
+            profit = excluded.profit,
+            revenue = excluded.revenue,
+            avg_popularity = excluded.avg_popularity,
+            rating_count = excluded.rating_count,
+            avg_rating = excluded.avg_rating,
+            adjusted_budget = excluded.adjusted_budget,
+            adjusted_profit = excluded.adjusted_profit,
+            adjusted_revenue = excluded.adjusted_revenue,
+            roi = excluded.roi,
+            median_profit = excluded.median_profit,
+            hit_rate = excluded.hit_rate,
+            flops = excluded.flops",
+    )?;
+
+    for (year, months) in &by_company {
+        for (month, companies) in months.iter().enumerate() {
+            for details in companies.values() {
+                stmt.execute(params![
+                    details.id,
+                    year,
+                    month + 1,
+                    details.metadata.movie_ids.len(),
+                    details.budget,
+                    details.profit,
+                    details.revenue,
+                    details.avg_populatarity,
+                    details.ratings.count,
+                    details.ratings.mean(),
+                    details.adjusted.map(|x| x.budget),
+                    details.adjusted.map(|x| x.profit),
+                    details.adjusted.map(|x| x.revenue),
+                    details.returns.roi(),
+                    details.returns.median_profit(),
+                    details.returns.hit_rate(),
+                    details.returns.flops,
+                ])?;
+
+                for (metric, sketch) in details.distributions.iter().flat_map(|x| x.by_name()) {
+                    let histogram: Vec<JsonValue> = sketch
+                        .histogram()
+                        .into_iter()
+                        .map(|(lower, upper, count)| array![lower, upper, count])
+                        .collect();
+                    distributions.execute(params![
+                        details.id,
+                        year,
+                        month + 1,
+                        metric,
+                        sketch.quantile(0.5),
+                        sketch.quantile(0.9),
+                        sketch.quantile(0.99),
+                        JsonValue::from(histogram).dump(),
+                    ])?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_distribution(s: &str) -> [u64; RATING_BUCKETS] {
+    let parsed = json::parse(s).unwrap_or(JsonValue::Null);
+    std::array::from_fn(|i| parsed[i].as_u64().unwrap_or_default())
+}
+
+pub fn load_sqlite(path: &str) -> rusqlite::Result<Catalog> {
+    let conn = Connection::open(path)?;
+    read_catalog(&conn)
+}
+
+// inverse of write_catalog, minus the aggregates which are cheap to rebuild.
+pub fn read_catalog(conn: &Connection) -> rusqlite::Result<Catalog> {
+    let genres = read_dictionary(conn, "genres")?;
+    let companies = read_dictionary(conn, "companies")?;
+    let collections = read_dictionary(conn, "collections")?;
+    let mut movie_genres = read_links(conn, "movie_genres", "genre_id")?;
+    let mut movie_companies = read_links(conn, "movie_companies", "company_id")?;
+    let mut movie_countries = read_links(conn, "movie_countries", "country_code")?;
+    let mut movie_languages = read_links(conn, "movie_languages", "language")?;
+    let countries = read_countries(conn)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, release_date, budget, revenue, profit, popularity, status, title, imdb_id,
+            runtime, original_language, collection_id, vote_average, vote_count, adult,
+            adjusted_budget, adjusted_revenue, adjusted_profit, lead_company, rating_count,
+            rating_mean, rating_distribution
+         FROM movies",
+    )?;
+    let movies = stmt
+        .query_map([], |row| {
+            let id: String = row.get(0)?;
+            let release_date: String = row.get(1)?;
+            let status: String = row.get(6)?;
+            let title: String = row.get(7)?;
+            let imdb_id: Option<String> = row.get(8)?;
+            let original_language: Option<String> = row.get(10)?;
+            let collection_id: Option<i64> = row.get(11)?;
+            let rating_count: u64 = row.get(19)?;
+            let rating_mean: Option<f64> = row.get(20)?;
+            let rating_distribution: Option<String> = row.get(21)?;
+            Ok(Movie {
+                genres: movie_genres.remove(&id).unwrap_or_default(),
+                production_companies: movie_companies.remove(&id).unwrap_or_default(),
+                production_countries: codes(movie_countries.remove(&id)),
+                spoken_languages: codes(movie_languages.remove(&id)),
+                release_date: NaiveDate::parse_from_str(&release_date, "%Y-%m-%d").map_err(
+                    |err| rusqlite::Error::FromSqlConversionFailure(1, Type::Text, Box::new(err)),
+                )?,
+                budget: row.get(2)?,
+                revenue: row.get(3)?,
+                profit: row.get(4)?,
+                avg_populatarity: row.get(5)?,
+                status: Status::from_str(&status),
+                id: KString::from(id),
+                title: KString::from(title),
+                imdb_id: imdb_id.map(KString::from),
+                runtime: row.get(9)?,
+                original_language: original_language.map(KString::from),
+                collection: collection_id.and_then(|id| {
+                    Some(Collection {
+                        id,
+                        name: collections.get(&id)?.clone(),
+                    })
+                }),
+                vote_average: row.get(12)?,
+                vote_count: row.get(13)?,
+                adult: row.get(14)?,
+                adjusted: match (row.get(15)?, row.get(16)?, row.get(17)?) {
+                    (Some(budget), Some(revenue), Some(profit)) => Some(Adjusted {
+                        budget,
+                        revenue,
+                        profit,
+                    }),
+                    _ => None,
+                },
+                lead_company: row.get(18)?,
+                // the sum is only kept as the mean, which is all it's used for.
+                ratings: RatingSummary {
+                    count: rating_count,
+                    sum: rating_mean.unwrap_or_default() * rating_count as f64,
+                    distribution: rating_distribution
+                        .as_deref()
+                        .map(read_distribution)
+                        .unwrap_or_default(),
+                },
+                ..Default::default()
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(Catalog {
+        movies,
+        genres,
+        companies,
+        countries,
+        ..Default::default()
+    })
+}
+
+fn read_dictionary(conn: &Connection, table: &str) -> rusqlite::Result<Dictionary> {
+    let mut stmt = conn.prepare(&format!("SELECT id, name FROM {table}"))?;
+    let rows = stmt.query_map([], |row| {
+        let name: String = row.get(1)?;
+        Ok((row.get(0)?, KString::from(name)))
+    })?;
+    rows.collect()
+}
+
+// keyed by country_id, like Catalog::countries.
+fn read_countries(conn: &Connection) -> rusqlite::Result<Dictionary> {
+    let mut stmt = conn.prepare("SELECT code, name FROM countries")?;
+    let rows = stmt.query_map([], |row| {
+        let code: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        Ok((country_id(&code), KString::from(name)))
+    })?;
+    rows.collect()
+}
+
+fn read_links<T: FromSql + Eq + Hash>(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+) -> rusqlite::Result<HashMap<String, HashSet<T>>> {
+    let mut stmt = conn.prepare(&format!("SELECT movie_id, {column} FROM {table}"))?;
+    let mut links: HashMap<String, HashSet<T>> = HashMap::new();
+    for row in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        let (movie_id, id) = row?;
+        links.entry(movie_id).or_default().insert(id);
+    }
+    Ok(links)
+}
+
+fn codes(links: Option<HashSet<String>>) -> HashSet<KString> {
+    links.into_iter().flatten().map(KString::from).collect()
+}
+
+mod tests;
@@ -0,0 +1,190 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+    use crate::{
+        data::{country_id, Collection, Movie, Status},
+        query::allocation::Allocation,
+    };
+    use chrono::NaiveDate;
+    use kstring::KString;
+    use std::collections::{BTreeMap, HashSet};
+
+    fn movie(id: &str, date: NaiveDate, companies: &[i64], revenue: i64) -> Movie {
+        Movie {
+            id: KString::from_ref(id),
+            genres: HashSet::from([28]),
+            production_companies: companies.iter().copied().collect(),
+            release_date: date,
+            budget: 10,
+            revenue,
+            profit: revenue - 10,
+            avg_populatarity: 1.5,
+            status: Status::Released,
+            ..Default::default()
+        }
+    }
+
+    fn catalog() -> Catalog {
+        Catalog {
+            movies: vec![
+                movie(
+                    "1",
+                    NaiveDate::from_ymd_opt(2010, 5, 1).unwrap(),
+                    &[7, 8],
+                    100,
+                ),
+                movie("2", NaiveDate::from_ymd_opt(2010, 5, 20).unwrap(), &[7], 50),
+            ],
+            genres: BTreeMap::from([(28, KString::from_static("Action"))]),
+            companies: BTreeMap::from([
+                (7, KString::from_static("Pixar")),
+                (8, KString::from_static("Disney")),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn write(conn: &mut Connection, catalog: &Catalog) {
+        write_catalog(conn, catalog, &Allocation::Full, false).unwrap();
+    }
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn writes_normalized_tables() {
+        let catalog = catalog();
+        let mut conn = Connection::open_in_memory().unwrap();
+        write(&mut conn, &catalog);
+
+        assert_eq!(count(&conn, "movies"), 2);
+        assert_eq!(count(&conn, "genres"), 1);
+        assert_eq!(count(&conn, "companies"), 2);
+        assert_eq!(count(&conn, "movie_genres"), 2);
+        assert_eq!(count(&conn, "movie_companies"), 3);
+        assert_eq!(count(&conn, "company_monthly"), 2);
+
+        let (movie_count, revenue): (i64, i64) = conn
+            .query_row(
+                "SELECT movie_count, revenue FROM company_monthly
+                 WHERE company_id = 7 AND year = 2010 AND month = 5",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(movie_count, 2);
+        assert_eq!(revenue, 150);
+    }
+
+    #[test]
+    fn rerunning_is_idempotent() {
+        let mut catalog = catalog();
+        let mut conn = Connection::open_in_memory().unwrap();
+        write(&mut conn, &catalog);
+
+        catalog.movies[0].revenue = 500;
+        catalog.movies[0].production_companies = HashSet::from([8]);
+        write(&mut conn, &catalog);
+
+        assert_eq!(count(&conn, "movies"), 2);
+        assert_eq!(count(&conn, "movie_companies"), 2);
+        assert_eq!(count(&conn, "company_monthly"), 2);
+
+        let revenue: i64 = conn
+            .query_row("SELECT revenue FROM movies WHERE id = '1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(revenue, 500);
+    }
+
+    #[test]
+    fn aggregates_cover_every_shard() {
+        let mut catalog = catalog();
+        let second = catalog.movies.pop().unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        write(&mut conn, &catalog);
+
+        catalog.movies = vec![second];
+        write(&mut conn, &catalog);
+
+        assert_eq!(count(&conn, "movies"), 2);
+        let (movie_count, revenue): (i64, i64) = conn
+            .query_row(
+                "SELECT movie_count, revenue FROM company_monthly
+                 WHERE company_id = 7 AND year = 2010 AND month = 5",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(movie_count, 2);
+        assert_eq!(revenue, 150);
+        assert_eq!(count(&conn, "company_monthly"), 2);
+    }
+
+    #[test]
+    fn read_catalog_round_trips() {
+        let mut catalog = catalog();
+        [4.0, 4.5, 2.0]
+            .iter()
+            .for_each(|x| catalog.movies[0].ratings.add(*x));
+        catalog.movies[0].title = KString::from_static("Toy Story");
+        catalog.movies[0].collection = Some(Collection {
+            id: 10194,
+            name: KString::from_static("Toy Story Collection"),
+        });
+        catalog.movies[0].production_countries = HashSet::from([KString::from_static("US")]);
+        catalog.movies[0].spoken_languages =
+            HashSet::from([KString::from_static("en"), KString::from_static("es")]);
+        catalog.countries = BTreeMap::from([(
+            country_id("US"),
+            KString::from_static("United States of America"),
+        )]);
+        let mut conn = Connection::open_in_memory().unwrap();
+        write(&mut conn, &catalog);
+
+        let loaded = read_catalog(&conn).unwrap();
+        assert_eq!(loaded.genres, catalog.genres);
+        assert_eq!(loaded.companies, catalog.companies);
+        assert_eq!(loaded.countries, catalog.countries);
+        assert_eq!(loaded.movies.len(), 2);
+
+        let first = loaded.movies.iter().find(|m| m.id == "1").unwrap();
+        assert_eq!(first.production_companies, HashSet::from([7, 8]));
+        assert_eq!(first.genres, HashSet::from([28]));
+        assert_eq!(first.release_date, catalog.movies[0].release_date);
+        assert_eq!(first.revenue, 100);
+        assert_eq!(first.status, Status::Released);
+        assert_eq!(first.title, "Toy Story");
+        assert_eq!(first.collection, catalog.movies[0].collection);
+        assert_eq!(
+            first.production_countries,
+            catalog.movies[0].production_countries
+        );
+        assert_eq!(first.spoken_languages, catalog.movies[0].spoken_languages);
+        assert_eq!(first.ratings.count, 3);
+        assert!((first.ratings.mean().unwrap() - 3.5).abs() < 1e-9);
+        assert_eq!(
+            first.ratings.distribution,
+            catalog.movies[0].ratings.distribution
+        );
+        let second = loaded.movies.iter().find(|m| m.id == "2").unwrap();
+        assert_eq!(second.ratings.mean(), None);
+        assert!(second.production_countries.is_empty());
+    }
+
+    #[test]
+    fn read_catalog_rejects_bad_dates() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        write(&mut conn, &catalog());
+        conn.execute("UPDATE movies SET release_date = 'soon' WHERE id = '1'", [])
+            .unwrap();
+
+        assert!(read_catalog(&conn).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::Datelike;
+use serde::Deserialize;
+use std::{collections::BTreeMap, io::Read};
+
+use crate::data::{Adjusted, Movie};
+
+// US CPI-U annual averages (BLS series CUUR0000SA0, 1982-84 = 100).
+const BUNDLED_CPI: &str = include_str!("../data/cpi.csv");
+
+#[derive(Debug, Deserialize)]
+struct CpiRowRaw {
+    year: i32,
+    index: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpiTable {
+    index: BTreeMap<i32, f64>,
+}
+
+impl CpiTable {
+    pub fn bundled() -> CpiTable {
+        CpiTable::from_reader(BUNDLED_CPI.as_bytes()).expect("bundled cpi table is valid")
+    }
+
+    // a `year,index` csv; any base period works since only ratios are used.
+    pub fn from_reader(reader: impl Read) -> Result<CpiTable, csv::Error> {
+        let index = csv::Reader::from_reader(reader)
+            .deserialize::<CpiRowRaw>()
+            .map(|row| row.map(|x| (x.year, x.index)))
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        Ok(CpiTable { index })
+    }
+
+    pub fn latest_year(&self) -> Option<i32> {
+        self.index.keys().last().copied()
+    }
+
+    // years outside the table use the nearest year it has.
+    pub fn index(&self, year: i32) -> Option<f64> {
+        self.index
+            .range(..=year)
+            .next_back()
+            .or_else(|| self.index.range(year..).next())
+            .map(|(_, index)| *index)
+    }
+
+    // multiply a `year` amount by this to get `base_year` dollars.
+    pub fn factor(&self, year: i32, base_year: i32) -> Option<f64> {
+        match (self.index(year), self.index(base_year)) {
+            (Some(from), Some(to)) if from > 0.0 => Some(to / from),
+            _ => None,
+        }
+    }
+}
+
+// fills in Movie::adjusted with constant `base_year` dollars; nominal values are untouched.
+pub fn adjust(movies: &mut [Movie], cpi: &CpiTable, base_year: i32) {
+    for movie in movies {
+        movie.adjusted = cpi
+            .factor(movie.release_date.year(), base_year)
+            .map(|factor| {
+                let budget = (movie.budget as f64 * factor).round() as i64;
+                let revenue = (movie.revenue as f64 * factor).round() as i64;
+                Adjusted {
+                    budget,
+                    revenue,
+                    profit: revenue - budget,
+                }
+            });
+    }
+}
+
+mod tests;
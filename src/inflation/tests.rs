@@ -0,0 +1,50 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn bundled_table() {
+        let cpi = CpiTable::bundled();
+        assert_eq!(cpi.index(1995), Some(152.4));
+        // clamped to the ends of the table.
+        assert_eq!(cpi.index(1900), cpi.index(1913));
+        assert_eq!(cpi.index(3000), cpi.index(cpi.latest_year().unwrap()));
+    }
+
+    #[test]
+    fn custom_table_and_factor() {
+        let cpi = CpiTable::from_reader("year,index\n2000,100\n2010,150\n".as_bytes()).unwrap();
+        assert_eq!(cpi.latest_year(), Some(2010));
+        assert_eq!(cpi.factor(2000, 2010), Some(1.5));
+        assert_eq!(cpi.factor(2005, 2010), Some(1.5));
+        assert_eq!(cpi.factor(2010, 2000), Some(100.0 / 150.0));
+
+        assert!(CpiTable::from_reader("year,index\nabc,1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn adjusts_movies() {
+        let cpi = CpiTable::from_reader("year,index\n2000,100\n2010,150\n".as_bytes()).unwrap();
+        let mut movies = vec![Movie {
+            release_date: NaiveDate::from_ymd_opt(2000, 5, 1).unwrap(),
+            budget: 10,
+            revenue: 30,
+            profit: 20,
+            ..Default::default()
+        }];
+
+        adjust(&mut movies, &cpi, 2010);
+
+        assert_eq!(movies[0].revenue, 30);
+        assert_eq!(
+            movies[0].adjusted,
+            Some(Adjusted {
+                budget: 15,
+                revenue: 45,
+                profit: 30
+            })
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use flate2::bufread::MultiGzDecoder;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// expands any glob patterns (e.g. "shards/2023-*.csv.gz"), in sorted order; plain paths and
+// `-` are kept as given so a missing file is reported when it's opened.
+pub fn expand_inputs(patterns: &[String]) -> Vec<String> {
+    patterns
+        .iter()
+        .flat_map(|pattern| {
+            if !pattern.contains(['*', '?', '[']) {
+                return vec![pattern.clone()];
+            }
+
+            let mut paths: Vec<String> = glob::glob(pattern)
+                .unwrap_or_else(|e| panic!("invalid glob {pattern}: {e}"))
+                .flatten()
+                .map(|x| x.to_string_lossy().into_owned())
+                .collect();
+            if paths.is_empty() {
+                panic!("no files match {pattern}!");
+            }
+            paths.sort();
+            paths
+        })
+        .collect()
+}
+
+// `-` is stdin, anything else a file; either may be gzip or zstd compressed.
+pub fn open_input(path: &str) -> io::Result<Box<dyn Read>> {
+    if path == "-" {
+        decompress(io::stdin())
+    } else {
+        decompress(File::open(path)?)
+    }
+}
+
+// sniffs the magic bytes without consuming them, so plain csv passes through untouched.
+pub fn decompress<R: Read + 'static>(reader: R) -> io::Result<Box<dyn Read>> {
+    let mut reader = BufReader::new(reader);
+    let head = reader.fill_buf()?;
+
+    if head.starts_with(&GZIP_MAGIC) {
+        // multi-member, so concatenated .gz files (cat a.gz b.gz) read as one.
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else if head.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+mod tests;
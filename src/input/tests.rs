@@ -0,0 +1,38 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    const CSV: &str = "id,title\n1,Toy Story\n";
+
+    fn read_all(reader: impl Read + 'static) -> String {
+        let mut out = String::new();
+        decompress(reader)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn plain_passes_through() {
+        assert_eq!(read_all(io::Cursor::new(CSV)), CSV);
+        assert_eq!(read_all(io::empty()), "");
+    }
+
+    #[test]
+    fn gzip() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(CSV.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(read_all(io::Cursor::new(compressed)), CSV);
+    }
+
+    #[test]
+    fn zstd() {
+        let compressed = zstd::encode_all(CSV.as_bytes(), 0).unwrap();
+        assert_eq!(read_all(io::Cursor::new(compressed)), CSV);
+    }
+}
@@ -1,34 +1,171 @@
 use chrono::NaiveDate;
-use fs::File;
-use std::{
-    borrow::BorrowMut,
-    collections::{BTreeMap, HashSet},
-    env, fs,
-};
+use std::{collections::BTreeMap, env, rc::Rc};
 
-use crate::{data::*, query::by_production_companies::*, query::*};
+use crate::{
+    config::Settings, data::*, query::by_production_companies::*, query::seasonality::MonthTotals,
+    query::*,
+};
 
+pub mod config;
 pub mod data;
+pub mod export;
+pub mod inflation;
+pub mod input;
 pub mod parsing;
+pub mod quality;
+pub mod report;
+pub mod serve;
+pub mod stats;
+pub mod validate;
+
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_COUNTRY: &str = "US";
+const REPORT_TOP: usize = 5;
+// a keyword on fewer movies than this is noise, not a theme.
+const KEYWORD_MIN_MOVIES: usize = 10;
+// fewer movies than this and a genre combination in one decade says little.
+const COMBINATION_MIN_MOVIES: usize = 5;
 
 fn main() {
     let args = env::args();
-    let config = parse_args(args.into_iter().by_ref());
+    let config = parse_args(args.into_iter().by_ref(), env::vars());
+
+    if config.command == Command::PrintConfig {
+        print!("{}", config.settings.to_toml());
+        return;
+    }
+
+    if config.command == Command::Stats {
+        for input_file in &config.input_files {
+            let file = input::open_input(input_file).expect("Couldn't read file...");
+            println!("{input_file}:");
+            stats::print_profile(&stats::profile(file));
+        }
+        return;
+    }
 
-    let file = File::open(&config.input_file).expect("Couldn't read file...");
-    println!("opened file for reading: {}", &config.input_file);
-    let res: Vec<Movie> = read_movie_metadata(&file, &config);
-    let distinct: HashSet<&Status> = res.iter().map(|x| &x.status).collect();
+    if config.command == Command::Validate {
+        let mut failed = false;
+        for input_file in &config.input_files {
+            let file = input::open_input(input_file).expect("Couldn't read file...");
+            println!("{input_file}:");
+            match validate::validate(file, validate::SAMPLE_ROWS) {
+                Ok(validation) => validate::print_validation(&validation),
+                Err(e) => {
+                    println!("{e}");
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut catalog = match &config.command {
+        Command::Serve {
+            sqlite: Some(sqlite),
+            ..
+        } => export::load_sqlite(sqlite).expect("Couldn't load sqlite export..."),
+        _ => {
+            let inputs = config.input_files.iter().map(|input_file| {
+                let file = input::open_input(input_file).expect("Couldn't read file...");
+                println!("opened file for reading: {input_file}");
+                (input_file.clone(), file)
+            });
+            read_movie_metadata(inputs, &config)
+                .unwrap_or_else(|(input_file, e)| panic!("invalid movies file {input_file}: {e}"))
+        }
+    };
+
+    if let Some(credits_file) = &config.credits_file {
+        let file = input::open_input(credits_file).expect("Couldn't read credits file...");
+        println!("joining credits from: {credits_file}");
+        read_credits(file, &mut catalog);
+    }
+
+    if let Some(keywords_file) = &config.keywords_file {
+        let file = input::open_input(keywords_file).expect("Couldn't read keywords file...");
+        println!("joining keywords from: {keywords_file}");
+        read_keywords(file, &mut catalog);
+    }
+
+    match (&config.links_file, &config.ratings_file) {
+        (Some(links_file), Some(ratings_file)) => {
+            let links = input::open_input(links_file).expect("Couldn't read links file...");
+            let ratings = input::open_input(ratings_file).expect("Couldn't read ratings file...");
+            println!("joining ratings from: {ratings_file} via {links_file}");
+            let skipped =
+                read_ratings(links, ratings, &mut catalog).expect("Couldn't read ratings file...");
+            if skipped > 0 {
+                println!("skipped {skipped} malformed ratings");
+            }
+        }
+        (None, None) => {}
+        _ => panic!("--links and --ratings must be given together!"),
+    }
 
-    for row in distinct {
-        println!("{:?}", row)
+    // before inflation so corrected values get adjusted too.
+    if let Some(mode) = config.quality {
+        quality::apply(&mut catalog, &config.heuristics, mode);
     }
 
+    if !catalog.rejects.is_empty() {
+        report::print_rejects_summary(&catalog.rejects);
+    }
+    if let Some(rejects_file) = &config.rejects_file {
+        report::write_rejects(rejects_file, &catalog.rejects)
+            .expect("Couldn't write rejects file...");
+        println!("wrote {} rejects to {rejects_file}", catalog.rejects.len());
+    }
+
+    if config.cpi_file.is_some() || config.base_year.is_some() {
+        let cpi = match &config.cpi_file {
+            Some(cpi_file) => {
+                let file = input::open_input(cpi_file).expect("Couldn't read cpi file...");
+                inflation::CpiTable::from_reader(file)
+                    .expect("invalid cpi file; expected year,index")
+            }
+            None => inflation::CpiTable::bundled(),
+        };
+        let base_year = config
+            .base_year
+            .or(cpi.latest_year())
+            .expect("cpi table is empty!");
+        println!("adjusting for inflation to {base_year} dollars");
+        inflation::adjust(&mut catalog.movies, &cpi, base_year);
+    }
+
+    let mut allocation = config.allocation.clone();
+    if let (allocation::Allocation::Weighted(weights), Some(weights_file)) =
+        (&mut allocation, &config.weights_file)
+    {
+        let file = input::open_input(weights_file).expect("Couldn't read weights file...");
+        *weights = allocation::read_weights(file)
+            .expect("invalid weights file; expected company_id,weight");
+        println!("read {} company weights from {weights_file}", weights.len());
+    }
+
+    if let Command::Serve { port, .. } = &config.command {
+        let api = serve::Api::new(
+            catalog,
+            config.distributions,
+            &allocation,
+            config.forecast_periods,
+            config.holdout_years,
+        );
+        serve::run(&api, *port);
+        return;
+    }
+
+    let res: &Vec<Movie> = &catalog.movies;
+
     let mut acc: BucketYearMap<ProdCompanyDetails> = BTreeMap::new();
 
-    let res: Vec<_> = res
+    let res: Vec<Rc<ProdCompanyDetails>> = res
         .iter()
-        .flat_map(|movie| movie_to_details(movie))
+        .flat_map(|x| movie_to_details(x, &allocation, config.distributions))
         .collect();
 
     // TODO: figure how to get fold + &mut to work.
@@ -51,6 +188,26 @@ fn main() {
     //   //  .take(5)
     //     .collect();
 
+    if let Command::Export { sqlite } = &config.command {
+        export::write_sqlite(sqlite, &catalog, &allocation, config.distributions)
+            .expect("Couldn't write sqlite export...");
+        println!("exported {} movies to {sqlite}", catalog.movies.len());
+        return;
+    }
+
+    if let Command::Report { company } = config.command {
+        if !report::print_company_report(&acc, &catalog, company, &allocation) {
+            println!("no releases for company {company}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if config.format == report::Format::Json {
+        print_ranking(&config, &catalog, &acc);
+        return;
+    }
+
     let flattened = flatten_bucket_year_map(&acc);
 
     flattened.iter().take(5).for_each(|(year, v)| {
@@ -59,6 +216,120 @@ fn main() {
         println!("{:?}", v)
     });
 
+    if config.distributions {
+        report::print_distributions(&acc, &catalog.companies, &allocation, REPORT_TOP);
+    }
+
+    let market = MonthTotals::of_movies(&catalog.movies);
+    let by_genre = bucket_movies(&catalog.movies, by_genres::movie_to_genre_details);
+    report::print_seasonality(
+        "company",
+        &acc,
+        &market,
+        &catalog.companies,
+        Some(&allocation),
+        REPORT_TOP,
+    );
+    report::print_seasonality(
+        "genre",
+        &by_genre,
+        &market,
+        &catalog.genres,
+        None,
+        REPORT_TOP,
+    );
+    report::print_market_share(&acc, &catalog.companies, &allocation, REPORT_TOP);
+    report::print_forecasts(
+        "company",
+        &acc,
+        &catalog.companies,
+        Some(&allocation),
+        config.forecast_periods,
+        config.holdout_years,
+        REPORT_TOP,
+    );
+    report::print_forecasts(
+        "genre",
+        &by_genre,
+        &catalog.genres,
+        None,
+        config.forecast_periods,
+        config.holdout_years,
+        REPORT_TOP,
+    );
+    report::print_peers(
+        &acc,
+        &catalog.companies,
+        &allocation,
+        peers::GenreSimilarity::Cosine,
+        peers::MIN_MOVIES,
+        REPORT_TOP,
+    );
+
+    let graph = coproduction::CoproductionGraph::of(&catalog.movies);
+    report::print_coproduction(&graph, &catalog.companies, REPORT_TOP * 2);
+    if let Some(graph_file) = &config.graph_file {
+        let out = if graph_file.ends_with(".dot") {
+            graph.to_dot(&catalog.companies)
+        } else {
+            graph.to_graphml(&catalog.companies)
+        };
+        std::fs::write(graph_file, out).expect("Couldn't write graph file...");
+        println!(
+            "wrote {} companies and {} partnerships to {graph_file}",
+            graph.nodes.len(),
+            graph.edges.len()
+        );
+    }
+
+    print_ranking(&config, &catalog, &acc);
+    report::print_country_split(&catalog.movies, &config.country);
+    report::print_country_company_breakdown(&catalog.movies, &catalog.companies, REPORT_TOP * 4);
+    report::print_franchises(&catalog.movies, REPORT_TOP * 2);
+    report::print_genre_combinations(
+        &catalog.movies,
+        &catalog.genres,
+        COMBINATION_MIN_MOVIES,
+        REPORT_TOP,
+    );
+
+    if config.credits_file.is_some() {
+        let by_director = bucket_movies(&catalog.movies, by_people::movie_to_director_details);
+        report::print_top_by_year(
+            "directors",
+            &by_director,
+            &catalog.people,
+            config.rank_by,
+            REPORT_TOP,
+        );
+
+        let by_actor = bucket_movies(&catalog.movies, by_people::movie_to_actor_details);
+        report::print_top_by_year(
+            "actors",
+            &by_actor,
+            &catalog.people,
+            config.rank_by,
+            REPORT_TOP,
+        );
+    }
+
+    if config.keywords_file.is_some() {
+        let by_keyword = bucket_movies(&catalog.movies, by_keywords::movie_to_keyword_details);
+        report::print_top_by_year(
+            "keywords",
+            &by_keyword,
+            &catalog.keywords,
+            Metric::Movies,
+            REPORT_TOP,
+        );
+        report::print_rising_keywords(
+            &by_keyword,
+            &catalog.keywords,
+            KEYWORD_MIN_MOVIES,
+            REPORT_TOP * 2,
+        );
+    }
+
     //.fold(&mut acc, |&mut acc, next| add_detail(acc, &next));
 
     // for row in res {
@@ -66,30 +337,366 @@ fn main() {
     // }
 }
 
+// the yearly ranking for --group-by, in --format.
+fn print_ranking(
+    config: &Config,
+    catalog: &Catalog,
+    by_company: &BucketYearMap<ProdCompanyDetails>,
+) {
+    let (format, group_by, metric) = (config.format, config.group_by, config.rank_by);
+    match group_by {
+        report::GroupBy::Country => {
+            let by_country = bucket_movies(&catalog.movies, by_country::movie_to_country_details);
+            report::print_ranking(
+                format,
+                group_by,
+                &by_country,
+                &catalog.countries,
+                metric,
+                REPORT_TOP,
+            )
+        }
+        report::GroupBy::Company => report::print_ranking(
+            format,
+            group_by,
+            by_company,
+            &catalog.companies,
+            metric,
+            REPORT_TOP,
+        ),
+        report::GroupBy::Genre => {
+            let by_genre = bucket_movies(&catalog.movies, by_genres::movie_to_genre_details);
+            report::print_ranking(
+                format,
+                group_by,
+                &by_genre,
+                &catalog.genres,
+                metric,
+                REPORT_TOP,
+            )
+        }
+    }
+}
+
 pub struct Config {
-    input_file: String,
+    // globs already expanded; several shards are read into one catalog.
+    input_files: Vec<String>,
     last_run: Option<NaiveDate>,
+    command: Command,
+    // companion credits.csv, joined on movie id when given.
+    credits_file: Option<String>,
+    // companion keywords.csv, joined on movie id when given.
+    keywords_file: Option<String>,
+    // MovieLens links.csv + ratings.csv, joined through the tmdb id when given.
+    links_file: Option<String>,
+    ratings_file: Option<String>,
+    // iso 3166-1 code the country report compares against the rest of the world.
+    country: String,
+    // either one turns on inflation adjustment; the bundled table and its latest year are the
+    // defaults for the other.
+    cpi_file: Option<String>,
+    base_year: Option<i32>,
+    // data quality checks are off unless a mode is given.
+    quality: Option<quality::Mode>,
+    heuristics: quality::Heuristics,
+    // csv of every row that was dropped or flagged, and why.
+    rejects_file: Option<String>,
+    // releases before this are skipped, like the ones after last_run.
+    since: Option<NaiveDate>,
+    // ordering for the country, director and actor rankings.
+    rank_by: Metric,
+    // per-company sketches of revenue, profit and popularity.
+    distributions: bool,
+    // where to write the co-production graph, if anywhere.
+    graph_file: Option<String>,
+    // how co-produced movies are credited to companies; a weighted table is empty until read
+    // from weights_file.
+    allocation: allocation::Allocation,
+    weights_file: Option<String>,
+    // months to forecast, and trailing years held out to backtest the forecasts.
+    forecast_periods: usize,
+    holdout_years: usize,
+    // text prints the whole report; json only the yearly ranking, grouped by group_by.
+    format: report::Format,
+    group_by: report::GroupBy,
+    // the merged layers everything above was resolved from.
+    settings: Settings,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    // default: ingest and print a sample of the aggregates.
+    Ingest,
+    // `export --sqlite path.db`
+    Export { sqlite: String },
+    // `serve [--port 8080] [--sqlite path.db]`, loads the export instead of the csv when given.
+    Serve { sqlite: Option<String>, port: u16 },
+    // `stats <input file>...`: profile each csv without ingesting it.
+    Stats,
+    // `validate <input file>...`: check each header and parse a sample of rows.
+    Validate,
+    // `config print`: show the merged settings.
+    PrintConfig,
+    // `report company <id> <input file>...`: one company's timeline and lifecycle.
+    Report { company: i64 },
 }
 
 // TODO: use clap? Probably overkill.
-fn parse_args(args: &mut impl Iterator<Item = String>) -> Config {
+// usage: moviedb [export --sqlite path.db] [--credits credits.csv] [--keywords keywords.csv]
+//                [--links links.csv --ratings ratings.csv] [--country US]
+//                [--cpi cpi.csv] [--base-year YYYY] [--since YYYY-MM]
+//                [--quality report|exclude|correct] [--min-budget N] [--min-revenue N]
+//                [--min-ratio X] [--max-ratio X] [--max-z X] [--rejects rejects.csv]
+//                [--rank-by revenue|profit|roi|hit_rate|...] [--distributions true]
+//                [--graph coproduction.graphml|coproduction.dot]
+//                [--allocation full|equal|lead|weighted [--weights weights.csv]]
+//                [--forecast-periods 12] [--holdout-years 1]
+//                [--format text|json] [--group-by country|company|genre]
+//                [--config moviedb.toml] <input file or glob>... [last run YYYY-MM]
+//        every input may be gzip or zstd compressed, and `-` reads from stdin.
+//        moviedb serve [--port 8080] (--sqlite path.db | <input file>... [last run YYYY-MM])
+//        moviedb (stats | validate) <input file>...
+//        moviedb report company <id> <input file>... [last run YYYY-MM]
+//        moviedb config print
+// every flag can also be set in the config file (`base_year = 2015`) or the environment
+// (MOVIEDB_BASE_YEAR=2015); flags beat the environment, which beats the file.
+fn parse_args(
+    args: &mut impl Iterator<Item = String>,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Config {
     args.next();
 
-    let input_file = args.next().expect("missing input file!");
+    let vars: Vec<(String, String)> = vars.collect();
+    let mut config_file = vars
+        .iter()
+        .find(|(name, _)| name == "MOVIEDB_CONFIG")
+        .map(|(_, value)| value.clone());
+
+    let mut positional = vec![];
+    let mut cli = Settings::default();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some("config") => config_file = Some(flag_value(args, &arg)),
+            Some(flag) => cli
+                .set(&flag.replace('-', "_"), &flag_value(args, &arg))
+                .unwrap_or_else(|e| panic!("{e}!")),
+            None => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter().peekable();
+    let command = positional
+        .next_if(|x| {
+            ["export", "serve", "stats", "validate", "config", "report"].contains(&x.as_str())
+        })
+        .unwrap_or_default();
+    if command == "config" && positional.next().as_deref() != Some("print") {
+        panic!("unknown config command; expected config print");
+    }
+    let report_company = if command == "report" {
+        if positional.next().as_deref() != Some("company") {
+            panic!("unknown report; expected report company <id>");
+        }
+        let id = positional.next().expect("report company requires an id!");
+        Some(
+            id.parse::<i64>()
+                .expect("invalid company id; expected a number"),
+        )
+    } else {
+        None
+    };
+
+    // a trailing YYYY-MM is the last run; everything else is an input.
+    let mut positional: Vec<String> = positional.collect();
+    if positional.last().is_some_and(|x| looks_like_month(x)) {
+        cli.last_run = positional.pop();
+    }
+    if !positional.is_empty() {
+        cli.inputs = Some(positional);
+    }
+
+    let file = config_file
+        .map(|path| Settings::from_file(&path).unwrap_or_else(|e| panic!("invalid config: {e}")))
+        .unwrap_or_default();
+    let env =
+        Settings::from_env(vars.into_iter()).unwrap_or_else(|e| panic!("invalid environment: {e}"));
+    let settings = default_settings().merge(file).merge(env).merge(cli);
+
+    let command = match command.as_str() {
+        "export" => Command::Export {
+            sqlite: settings
+                .sqlite
+                .clone()
+                .expect("export requires --sqlite <path>!"),
+        },
+        "serve" => Command::Serve {
+            sqlite: settings.sqlite.clone(),
+            port: settings.port.unwrap_or(DEFAULT_PORT),
+        },
+        "stats" => Command::Stats,
+        "validate" => Command::Validate,
+        "config" => Command::PrintConfig,
+        "report" => Command::Report {
+            company: report_company.expect("parsed above"),
+        },
+        _ => Command::Ingest,
+    };
+
+    // serving from an export and printing the config are the only modes that don't read the csv.
+    let input_files = input::expand_inputs(&settings.inputs.clone().unwrap_or_default());
+    let needs_input = !matches!(
+        &command,
+        Command::Serve {
+            sqlite: Some(_),
+            ..
+        } | Command::PrintConfig
+    );
+    if input_files.is_empty() && needs_input {
+        panic!("missing input file!");
+    }
 
-    let last_run = args.next().map(|s| {
+    let last_run = settings.last_run.as_deref().map(|s| {
         println!("last run: {s}");
-        let expand_date: String = format!("{s}-01");
-        NaiveDate::parse_from_str(&expand_date, "%Y-%m-%d")
-            .expect("invalid last run; expected YYYY-MM")
+        parse_month(s).expect("invalid last run; expected YYYY-MM")
+    });
+    let since = settings
+        .since
+        .as_deref()
+        .map(|s| parse_month(s).expect("invalid since; expected YYYY-MM"));
+    let quality = settings.quality.as_deref().map(|x| {
+        quality::Mode::from_name(x).expect("invalid quality; expected report, exclude or correct")
     });
+    let rank_by = settings
+        .rank_by
+        .as_deref()
+        .map(|x| Metric::from_name(x).expect("invalid rank by; expected a metric name"))
+        .unwrap_or(Metric::Revenue);
+    if let Some(graph) = &settings.graph {
+        if !graph.ends_with(".graphml") && !graph.ends_with(".dot") {
+            panic!("invalid graph; expected a .graphml or .dot path");
+        }
+    }
+    let allocation = settings
+        .allocation
+        .as_deref()
+        .map(|x| {
+            allocation::Allocation::from_name(x)
+                .expect("invalid allocation; expected full, equal, lead or weighted")
+        })
+        .unwrap_or(allocation::Allocation::Full);
+    match (&allocation, &settings.weights) {
+        (allocation::Allocation::Weighted(_), None) => {
+            panic!("--allocation weighted requires --weights <path>!")
+        }
+        (allocation::Allocation::Weighted(_), Some(_)) | (_, None) => {}
+        (_, Some(_)) => panic!("--weights only applies to --allocation weighted!"),
+    }
+    let forecast_periods = settings
+        .forecast_periods
+        .unwrap_or(forecast::DEFAULT_PERIODS);
+    if forecast_periods > forecast::MAX_PERIODS {
+        panic!(
+            "invalid forecast periods; expected at most {}",
+            forecast::MAX_PERIODS
+        );
+    }
+    let holdout_years = settings
+        .holdout_years
+        .unwrap_or(forecast::DEFAULT_HOLDOUT_YEARS);
+    if holdout_years > forecast::MAX_HOLDOUT_YEARS {
+        panic!(
+            "invalid holdout years; expected at most {}",
+            forecast::MAX_HOLDOUT_YEARS
+        );
+    }
+    let format = settings
+        .format
+        .as_deref()
+        .map(|x| report::Format::from_name(x).expect("invalid format; expected text or json"))
+        .unwrap_or(report::Format::Text);
+    let group_by = settings
+        .group_by
+        .as_deref()
+        .map(|x| {
+            report::GroupBy::from_name(x)
+                .expect("invalid group by; expected country, company or genre")
+        })
+        .unwrap_or(report::GroupBy::Country);
+    let defaults = quality::Heuristics::default();
+    let heuristics = quality::Heuristics {
+        min_budget: settings.min_budget.unwrap_or(defaults.min_budget),
+        min_revenue: settings.min_revenue.unwrap_or(defaults.min_revenue),
+        min_ratio: settings.min_ratio.unwrap_or(defaults.min_ratio),
+        max_ratio: settings.max_ratio.unwrap_or(defaults.max_ratio),
+        max_z: settings.max_z.unwrap_or(defaults.max_z),
+    };
 
     Config {
-        input_file,
+        input_files,
         last_run,
+        since,
+        command,
+        credits_file: settings.credits.clone(),
+        keywords_file: settings.keywords.clone(),
+        links_file: settings.links.clone(),
+        ratings_file: settings.ratings.clone(),
+        country: settings.country.clone().unwrap_or_default().to_uppercase(),
+        cpi_file: settings.cpi.clone(),
+        base_year: settings.base_year,
+        quality,
+        heuristics,
+        rejects_file: settings.rejects.clone(),
+        rank_by,
+        distributions: settings.distributions.unwrap_or_default(),
+        graph_file: settings.graph.clone(),
+        allocation,
+        weights_file: settings.weights.clone(),
+        forecast_periods,
+        holdout_years,
+        format,
+        group_by,
+        settings,
     }
 }
 
+// what `config print` shows for anything nobody set.
+fn default_settings() -> Settings {
+    let heuristics = quality::Heuristics::default();
+    Settings {
+        country: Some(DEFAULT_COUNTRY.to_string()),
+        port: Some(DEFAULT_PORT),
+        rank_by: Some(Metric::Revenue.name().to_string()),
+        distributions: Some(false),
+        allocation: Some(allocation::Allocation::Full.name().to_string()),
+        forecast_periods: Some(forecast::DEFAULT_PERIODS),
+        holdout_years: Some(forecast::DEFAULT_HOLDOUT_YEARS),
+        format: Some(report::Format::Text.name().to_string()),
+        group_by: Some(report::GroupBy::Country.name().to_string()),
+        min_budget: Some(heuristics.min_budget),
+        min_revenue: Some(heuristics.min_revenue),
+        min_ratio: Some(heuristics.min_ratio),
+        max_ratio: Some(heuristics.max_ratio),
+        max_z: Some(heuristics.max_z),
+        ..Default::default()
+    }
+}
+
+fn parse_month(s: &str) -> chrono::ParseResult<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{s}-01"), "%Y-%m-%d")
+}
+
+fn flag_value(args: &mut impl Iterator<Item = String>, flag: &str) -> String {
+    args.next()
+        .unwrap_or_else(|| panic!("missing value for {flag}!"))
+}
+
+// YYYY-MM, loosely; parsing it properly happens once it's known to be the last run.
+fn looks_like_month(s: &str) -> bool {
+    s.len() == 7
+        && s.char_indices()
+            .all(|(i, c)| if i == 4 { c == '-' } else { c.is_ascii_digit() })
+}
+
 mod query;
 
 #[cfg(test)]
@@ -106,18 +713,148 @@ mod tests {
             "2021-07".to_string(),
         ]
         .into_iter();
-        let config = parse_args(args);
-        assert_eq!(config.input_file, "input.txt");
+        let config = parse_args(args, std::iter::empty());
+        assert_eq!(config.input_files, vec!["input.txt"]);
         assert_eq!(config.last_run.unwrap().year(), 2021);
         assert_eq!(config.last_run.unwrap().month(), 7);
+        assert_eq!(config.command, Command::Ingest);
+        assert_eq!(config.format, report::Format::Text);
+        assert_eq!(config.group_by, report::GroupBy::Country);
+
+        // Test case 2: export mode, flag before the positional args
+        let args = &mut vec![
+            "program_name".to_string(),
+            "export".to_string(),
+            "--sqlite".to_string(),
+            "movies.db".to_string(),
+            "input.txt".to_string(),
+        ]
+        .into_iter();
+        let config = parse_args(args, std::iter::empty());
+        assert_eq!(config.input_files, vec!["input.txt"]);
+        assert_eq!(config.last_run, None);
+        assert_eq!(
+            config.command,
+            Command::Export {
+                sqlite: "movies.db".to_string()
+            }
+        );
+
+        // Test case 3: serving a previous export needs no input file
+        let args = &mut vec![
+            "program_name".to_string(),
+            "serve".to_string(),
+            "--sqlite".to_string(),
+            "movies.db".to_string(),
+            "--port".to_string(),
+            "9000".to_string(),
+        ]
+        .into_iter();
+        let config = parse_args(args, std::iter::empty());
+        assert!(config.input_files.is_empty());
+        assert_eq!(
+            config.command,
+            Command::Serve {
+                sqlite: Some("movies.db".to_string()),
+                port: 9000
+            }
+        );
+
+        // Test case 4: quality checks with an overridden threshold
+        let args = &mut vec![
+            "program_name".to_string(),
+            "--quality".to_string(),
+            "correct".to_string(),
+            "--min-budget".to_string(),
+            "5000".to_string(),
+            "input.txt".to_string(),
+        ]
+        .into_iter();
+        let config = parse_args(args, std::iter::empty());
+        assert_eq!(config.quality, Some(quality::Mode::Correct));
+        assert_eq!(config.heuristics.min_budget, 5000);
+        assert_eq!(config.heuristics.max_z, 3.0);
 
-        // Test case 2: Missing input file argument
+        // Test case 5: profiling only needs the input file
+        let args = &mut vec![
+            "program_name".to_string(),
+            "stats".to_string(),
+            "input.txt".to_string(),
+        ]
+        .into_iter();
+        let config = parse_args(args, std::iter::empty());
+        assert_eq!(config.input_files, vec!["input.txt"]);
+        assert_eq!(config.command, Command::Stats);
+
+        // Test case 6: several shards and a last run
+        let args = &mut vec![
+            "program_name".to_string(),
+            "2023-01.csv".to_string(),
+            "2023-02.csv.gz".to_string(),
+            "2023-02".to_string(),
+        ]
+        .into_iter();
+        let config = parse_args(args, std::iter::empty());
+        assert_eq!(config.input_files, vec!["2023-01.csv", "2023-02.csv.gz"]);
+        assert_eq!(config.last_run.unwrap().month(), 2);
+
+        // Test case 7: config file, environment and flags, in increasing precedence
+        let path = env::temp_dir().join(format!("moviedb-{}-config.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "inputs = [\"from-file.csv\"]\ncountry = \"gb\"\nbase_year = 2000\nport = 9000\n",
+        )
+        .unwrap();
+        let args = &mut vec![
+            "program_name".to_string(),
+            "--config".to_string(),
+            path.to_string_lossy().into_owned(),
+            "--base-year".to_string(),
+            "2015".to_string(),
+            "--group-by".to_string(),
+            "genre".to_string(),
+            "config".to_string(),
+            "print".to_string(),
+        ]
+        .into_iter();
+        let vars = [
+            ("MOVIEDB_BASE_YEAR", "2010"),
+            ("MOVIEDB_SINCE", "1990-01"),
+            ("MOVIEDB_FORMAT", "json"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let config = parse_args(args, vars.into_iter());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.command, Command::PrintConfig);
+        assert_eq!(config.input_files, vec!["from-file.csv"]);
+        assert_eq!(config.country, "GB");
+        assert_eq!(config.base_year, Some(2015));
+        assert_eq!(config.since.unwrap().year(), 1990);
+        assert_eq!(config.settings.port, Some(9000));
+        assert_eq!(config.settings.max_z, Some(3.0));
+        assert_eq!(config.format, report::Format::Json);
+        assert_eq!(config.group_by, report::GroupBy::Genre);
+
+        // Test case 8: a single company's report
+        let args = &mut vec![
+            "program_name".to_string(),
+            "report".to_string(),
+            "company".to_string(),
+            "3".to_string(),
+            "input.txt".to_string(),
+        ]
+        .into_iter();
+        let config = parse_args(args, std::iter::empty());
+        assert_eq!(config.input_files, vec!["input.txt"]);
+        assert_eq!(config.command, Command::Report { company: 3 });
+
+        // Test case 9: Missing input file argument
         // let args = &mut vec!["program_name".to_string()].into_iter();
-        // let config = parse_args(args);
-        // assert_eq!(config.input_file, ""); // Assuming empty string is the default value for input_file
+        // let config = parse_args(args, std::iter::empty());
+        // assert!(config.input_files.is_empty()); // Assuming no inputs is the default
         // assert_eq!(config.last_run, None);
 
-        // Test case 3: Invalid last run argument format
+        // Test case 10: Invalid last run argument format
         // let args = &mut vec![
         //     "program_name".to_string(),
         //     "input.txt".to_string(),
@@ -127,4 +864,13 @@ mod tests {
         // let result = std::panic::catch_unwind(|| parse_args(args));
         // assert!(result.is_err());
     }
+
+    #[test]
+    #[should_panic(expected = "invalid forecast periods")]
+    fn test_parse_args_caps_forecast_periods() {
+        let args = &mut ["program_name", "--forecast-periods", "100000", "input.txt"]
+            .map(String::from)
+            .into_iter();
+        parse_args(args, std::iter::empty());
+    }
 }
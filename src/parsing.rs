@@ -1,10 +1,12 @@
 use chrono::NaiveDate;
 use csv::StringRecord;
+use itertools::Itertools;
+use json::JsonValue;
 use kstring::KString;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
-use crate::data::{Movie, Status};
+use crate::data::{country_id, Collection, Dictionary, Filtered, Movie, Status};
 
 #[derive(Debug, Deserialize)]
 pub struct MovieRowRaw {
@@ -17,53 +19,487 @@ pub struct MovieRowRaw {
     #[serde(deserialize_with = "csv::invalid_option", rename = "popularity")]
     avg_populatarity: Option<f32>,
     status: String,
+    // everything below is optional: older exports may not have the column, and blanks or the
+    // handful of rows with shifted columns come through as None rather than failing the row.
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    imdb_id: String,
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    runtime: Option<f32>,
+    #[serde(default)]
+    original_language: String,
+    #[serde(default)]
+    spoken_languages: String,
+    #[serde(default)]
+    production_countries: String,
+    #[serde(default)]
+    belongs_to_collection: String,
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    vote_average: Option<f32>,
+    // some exports write counts as floats, e.g. "5415.0".
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    vote_count: Option<f64>,
+    #[serde(default)]
+    adult: String,
+}
+
+// columns MovieRowRaw can't do without.
+pub const REQUIRED_COLUMNS: [&str; 8] = [
+    "id",
+    "genres",
+    "production_companies",
+    "release_date",
+    "budget",
+    "revenue",
+    "popularity",
+    "status",
+];
+
+// read when present, defaulted when not.
+pub const OPTIONAL_COLUMNS: [&str; 10] = [
+    "title",
+    "imdb_id",
+    "runtime",
+    "original_language",
+    "spoken_languages",
+    "production_countries",
+    "belongs_to_collection",
+    "vote_average",
+    "vote_count",
+    "adult",
+];
+
+// in the TMDB export but not used; not worth warning about.
+const IGNORED_COLUMNS: [&str; 6] = [
+    "homepage",
+    "original_title",
+    "overview",
+    "poster_path",
+    "tagline",
+    "video",
+];
+
+// other names the same column has gone by in older or hand-made exports.
+const COLUMN_ALIASES: [(&str, &str); 4] = [
+    ("avg_popularity", "popularity"),
+    ("tmdb_id", "id"),
+    ("release", "release_date"),
+    ("companies", "production_companies"),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct Schema {
+    // the file's header with aliases renamed, ready for from_record.
+    pub headers: StringRecord,
+    // (alias, column) pairs that were renamed.
+    pub aliased: Vec<(String, String)>,
+    // neither used, ignored nor an alias; likely a typo or a renamed column.
+    pub unknown: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SchemaError {
+    pub missing: Vec<String>,
+    pub unknown: Vec<String>,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "missing columns: {}", self.missing.join(", "))?;
+        if !self.unknown.is_empty() {
+            write!(f, "; unknown columns: {}", self.unknown.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// trims and lowercases the header and renames aliases, unless the real column is also there.
+pub fn normalize_headers(headers: &StringRecord) -> (StringRecord, Vec<(String, String)>) {
+    let names = headers
+        .iter()
+        .map(|x| x.trim().to_lowercase())
+        .collect_vec();
+    let mut aliased = vec![];
+
+    let normalized = names
+        .iter()
+        .map(
+            |name| match COLUMN_ALIASES.iter().find(|(alias, _)| alias == name) {
+                Some((alias, column)) if !names.iter().any(|x| x == column) => {
+                    aliased.push((alias.to_string(), column.to_string()));
+                    column.to_string()
+                }
+                _ => name.clone(),
+            },
+        )
+        .collect();
+
+    (normalized, aliased)
+}
+
+// the columns of a normalized header MovieRowRaw actually reads.
+pub fn used_columns(headers: &StringRecord) -> BTreeSet<String> {
+    headers
+        .iter()
+        .filter(|x| REQUIRED_COLUMNS.contains(x) || OPTIONAL_COLUMNS.contains(x))
+        .map(String::from)
+        .collect()
+}
+
+pub fn check_schema(headers: &StringRecord) -> Result<Schema, SchemaError> {
+    let (headers, aliased) = normalize_headers(headers);
+
+    let missing = REQUIRED_COLUMNS
+        .iter()
+        .filter(|column| !headers.iter().any(|x| x == **column))
+        .map(|x| x.to_string())
+        .collect_vec();
+    let unknown = headers
+        .iter()
+        .filter(|x| {
+            !REQUIRED_COLUMNS.contains(x)
+                && !OPTIONAL_COLUMNS.contains(x)
+                && !IGNORED_COLUMNS.contains(x)
+        })
+        .map(String::from)
+        .collect_vec();
+
+    if missing.is_empty() {
+        Ok(Schema {
+            headers,
+            aliased,
+            unknown,
+        })
+    } else {
+        Err(SchemaError { missing, unknown })
+    }
 }
 
 pub fn from_record(
     record: &StringRecord,
     headers: &StringRecord,
 ) -> Result<MovieRowRaw, csv::Error> {
-    record.deserialize(Some(&headers))
+    record.deserialize(Some(headers))
 }
 
 impl MovieRowRaw {
-    // TODO: This should probably return result instead.
-    pub fn to_movie(&self, last_run: &Option<NaiveDate>) -> Option<Movie> {
+    pub fn id(&self) -> KString {
+        KString::from(&self.id)
+    }
+
+    pub fn budget(&self) -> i64 {
+        self.budget
+    }
+
+    pub fn revenue(&self) -> i64 {
+        self.revenue
+    }
+
+    pub fn popularity(&self) -> Option<f32> {
+        self.avg_populatarity
+    }
+
+    pub fn release_date(&self) -> NaiveDate {
+        self.release_date
+    }
+
+    // as written in the file, e.g. "Post Production"; Status only knows Released.
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn to_movie(&self, last_run: &Option<NaiveDate>) -> Result<Movie, Filtered> {
         // TODO: only status == released is valid at the moment.
-        if self.revenue > 0
-            && last_run.map(|x| self.release_date <= x).unwrap_or(true)
-            && Status::from_str(&self.status) == Status::Released
-        {
+        if self.revenue <= 0 {
+            Err(Filtered::NoRevenue)
+        } else if !last_run.map(|x| self.release_date <= x).unwrap_or(true) {
+            Err(Filtered::AfterLastRun)
+        } else if Status::from_str(&self.status) != Status::Released {
+            Err(Filtered::NotReleased)
+        } else {
+            let vote_count = self
+                .vote_count
+                .filter(|x| x.is_finite() && *x >= 0.0)
+                .map(|x| x as u32);
             // pull genres and production companies.
-            Some(Movie {
+            Ok(Movie {
                 id: KString::from(&self.id),
                 genres: convert_json_to_set(&self.genres),
                 production_companies: convert_json_to_set(&self.production_companies),
+                lead_company: convert_json_to_first(&self.production_companies),
                 release_date: self.release_date,
                 budget: self.budget,
                 revenue: self.revenue,
                 avg_populatarity: self.avg_populatarity.unwrap_or(0.0),
                 status: Status::Released,
                 profit: self.revenue - self.budget,
+                title: KString::from_ref(self.title.trim()),
+                imdb_id: non_blank(&self.imdb_id),
+                // 0 minutes is how the dataset spells unknown.
+                runtime: self.runtime.filter(|x| *x > 0.0),
+                original_language: non_blank(&self.original_language),
+                spoken_languages: convert_json_to_codes(&self.spoken_languages, "iso_639_1"),
+                production_countries: convert_json_to_codes(
+                    &self.production_countries,
+                    "iso_3166_1",
+                ),
+                collection: convert_json_to_collection(&self.belongs_to_collection),
+                // an average over zero votes is a placeholder, not a score.
+                vote_average: self.vote_average.filter(|_| vote_count.unwrap_or(0) > 0),
+                vote_count,
+                adult: match self.adult.trim() {
+                    "True" | "true" => Some(true),
+                    "False" | "false" => Some(false),
+                    _ => None,
+                },
+                // joined in later from the companion files.
+                ..Default::default()
             })
-        } else {
-            None
         }
     }
+
+    pub fn genre_names(&self) -> Dictionary {
+        convert_json_to_dictionary(&self.genres)
+    }
+
+    pub fn company_names(&self) -> Dictionary {
+        convert_json_to_dictionary(&self.production_companies)
+    }
+
+    pub fn country_names(&self) -> Dictionary {
+        parse_json_cell(&self.production_countries)
+            .map(|v| {
+                v.members()
+                    .flat_map(|obj| {
+                        let code = obj["iso_3166_1"].as_str()?;
+                        let name = obj["name"].as_str()?;
+                        Some((country_id(code), KString::from_ref(name)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreditsRowRaw {
+    id: String,
+    cast: String,
+    crew: String,
+}
+
+// one person on a movie, in billing order for cast.
+#[derive(Debug, PartialEq)]
+pub struct Credit {
+    pub id: i64,
+    pub name: KString,
+}
+
+impl CreditsRowRaw {
+    pub fn id(&self) -> KString {
+        KString::from(&self.id)
+    }
+
+    pub fn cast(&self) -> Vec<Credit> {
+        let mut cast = parse_json_cell(&self.cast)
+            .map(|v| {
+                v.members()
+                    .flat_map(|obj| Some((obj["order"].as_i64()?, to_credit(obj)?)))
+                    .collect_vec()
+            })
+            .unwrap_or_default();
+        cast.sort_by_key(|(order, _)| *order);
+        cast.into_iter().map(|(_, credit)| credit).collect()
+    }
+
+    pub fn crew_with_job(&self, job: &str) -> Vec<Credit> {
+        parse_json_cell(&self.crew)
+            .map(|v| {
+                v.members()
+                    .filter(|obj| obj["job"].as_str() == Some(job))
+                    .flat_map(to_credit)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeywordsRowRaw {
+    id: String,
+    keywords: String,
+}
+
+impl KeywordsRowRaw {
+    pub fn id(&self) -> KString {
+        KString::from(&self.id)
+    }
+
+    pub fn keyword_ids(&self) -> HashSet<i64> {
+        convert_json_to_set(&self.keywords)
+    }
+
+    pub fn keyword_names(&self) -> Dictionary {
+        convert_json_to_dictionary(&self.keywords)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkRowRaw {
+    #[serde(rename = "movieId")]
+    movie_id: i64,
+    #[serde(rename = "tmdbId", deserialize_with = "csv::invalid_option")]
+    tmdb_id: Option<i64>,
+}
+
+impl LinkRowRaw {
+    pub fn movie_id(&self) -> i64 {
+        self.movie_id
+    }
+
+    pub fn tmdb_id(&self) -> Option<KString> {
+        self.tmdb_id.map(|x| KString::from_string(x.to_string()))
+    }
+}
+
+// userId and timestamp aren't needed, so they're never deserialized.
+#[derive(Debug, Deserialize)]
+pub struct RatingRowRaw {
+    #[serde(rename = "movieId")]
+    movie_id: i64,
+    rating: f32,
+}
+
+impl RatingRowRaw {
+    pub fn movie_id(&self) -> i64 {
+        self.movie_id
+    }
+
+    pub fn rating(&self) -> f32 {
+        self.rating
+    }
+}
+
+fn to_credit(obj: &JsonValue) -> Option<Credit> {
+    Some(Credit {
+        id: obj["id"].as_i64()?,
+        name: KString::from_ref(obj["name"].as_str()?),
+    })
+}
+
+// the cells are python reprs rather than json: single quoted strings (double quoted when the
+// text has an apostrophe) and None/True/False. rewrite them token by token so names like
+// "O'Brien" survive.
+fn python_literal_to_json(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => match c {
+                '\\' => match chars.next() {
+                    Some('\'') => out.push('\''),
+                    Some(escaped) => {
+                        out.push('\\');
+                        out.push(escaped);
+                    }
+                    None => {}
+                },
+                '"' if q == '\'' => out.push_str("\\\""),
+                c if c == q => {
+                    out.push('"');
+                    quote = None;
+                }
+                c => out.push(c),
+            },
+            None => match c {
+                '\'' | '"' => {
+                    out.push('"');
+                    quote = Some(c);
+                }
+                c if c.is_ascii_alphabetic() => {
+                    let mut word = String::from(c);
+                    while let Some(next) = chars.next_if(|x| x.is_ascii_alphanumeric()) {
+                        word.push(next);
+                    }
+                    out.push_str(match word.as_str() {
+                        "None" => "null",
+                        "True" => "true",
+                        "False" => "false",
+                        other => other,
+                    });
+                }
+                c => out.push(c),
+            },
+        }
+    }
+
+    out
+}
+
+fn parse_json_cell(s: &str) -> json::Result<JsonValue> {
+    json::parse(&python_literal_to_json(s))
+}
+
+fn non_blank(s: &str) -> Option<KString> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| KString::from_ref(s))
+}
+
+// e.g. production_countries -> {"US", "GB"} keyed on "iso_3166_1".
+fn convert_json_to_codes(s: &str, key: &str) -> HashSet<KString> {
+    parse_json_cell(s)
+        .map(|v| {
+            v.members()
+                .flat_map(|obj| obj[key].as_str())
+                .map(KString::from_ref)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// belongs_to_collection is a single object (or blank), not a list.
+fn convert_json_to_collection(s: &str) -> Option<Collection> {
+    let parsed = parse_json_cell(s).ok()?;
+    Some(Collection {
+        id: parsed["id"].as_i64()?,
+        name: KString::from_ref(parsed["name"].as_str()?),
+    })
 }
 
 fn convert_json_to_set(s: &str) -> HashSet<i64> {
-    let s = s.replace("'", "\"");
-    //println!("raw: {}", s);
-    let parsed = json::parse(&s);
+    let parsed = parse_json_cell(s);
+
+    parsed
+        .map(|v| v.members().flat_map(|obj| obj["id"].as_i64()).collect())
+        .unwrap_or_default()
+}
+
+// id of the first entry, in file order.
+fn convert_json_to_first(s: &str) -> Option<i64> {
+    parse_json_cell(s)
+        .ok()?
+        .members()
+        .find_map(|obj| obj["id"].as_i64())
+}
+
+// same shape as convert_json_to_set, but keeps the name alongside the id.
+fn convert_json_to_dictionary(s: &str) -> Dictionary {
+    let parsed = parse_json_cell(s);
 
     parsed
         .map(|v| {
             v.members()
-                .flat_map(|obj| obj["id"].as_i64().map(|x| x))
+                .flat_map(|obj| {
+                    let id = obj["id"].as_i64()?;
+                    let name = obj["name"].as_str()?;
+                    Some((id, KString::from_ref(name)))
+                })
                 .collect()
         })
-        .unwrap_or(HashSet::new())
+        .unwrap_or_default()
 }
 
 mod tests;
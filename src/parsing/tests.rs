@@ -1,8 +1,8 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use std::collections::hash_set;
-
     use super::super::*;
+    use csv::StringRecord;
 
     #[test]
     fn json_to_set() {
@@ -14,6 +14,285 @@ mod tests {
         let json = json_single_quotes.replace("'", "\"");
         let expected = HashSet::from([28, 12, 878, 53]);
         assert_eq!(convert_json_to_set(&json), expected);
-        assert_eq!(convert_json_to_set(&json_single_quotes), expected);
+        assert_eq!(convert_json_to_set(json_single_quotes), expected);
+    }
+
+    #[test]
+    fn python_literals() {
+        assert_eq!(
+            python_literal_to_json("[{'a': None, 'b': True, 'c': False}]"),
+            r#"[{"a": null, "b": true, "c": false}]"#
+        );
+        // double quoted when the text has an apostrophe, escaped when it has both.
+        assert_eq!(
+            python_literal_to_json(r#"[{'name': "O'Brien"}, {'name': 'a \'b\' "c"'}]"#),
+            r#"[{"name": "O'Brien"}, {"name": "a 'b' \"c\""}]"#
+        );
+        // keywords inside strings are left alone.
+        assert_eq!(python_literal_to_json("['None']"), r#"["None"]"#);
+
+        let companies = r#"[{'name': "Dune's Edge", 'id': 7}, {'name': 'Pixar', 'id': 3}]"#;
+        assert_eq!(convert_json_to_set(companies), HashSet::from([7, 3]));
+        // the set loses the order, so the lead is kept on its own.
+        assert_eq!(convert_json_to_first(companies), Some(7));
+        assert_eq!(convert_json_to_first("[]"), None);
+    }
+
+    #[test]
+    fn credits_cast_and_crew() {
+        let row = CreditsRowRaw {
+            id: "862".to_string(),
+            cast: r#"[{'cast_id': 14, 'character': 'Woody (voice)', 'credit_id': '52fe4284c3a36847f8024f95', 'gender': 2, 'id': 31, 'name': 'Tom Hanks', 'order': 1, 'profile_path': None}, {'cast_id': 15, 'character': "Buzz Lightyear's voice", 'credit_id': '52fe4284c3a36847f8024f99', 'gender': 2, 'id': 12898, 'name': 'Tim Allen', 'order': 0, 'profile_path': '/x.jpg'}]"#.to_string(),
+            crew: r#"[{'credit_id': '52fe4284c3a36847f8024f49', 'department': 'Directing', 'gender': 2, 'id': 7879, 'job': 'Director', 'name': 'John Lasseter', 'profile_path': None}, {'credit_id': '52fe4284c3a36847f8024f4f', 'department': 'Writing', 'gender': 2, 'id': 12891, 'job': 'Screenplay', 'name': 'Joss Whedon', 'profile_path': None}]"#.to_string(),
+        };
+
+        assert_eq!(row.id(), "862");
+        let cast = row.cast();
+        assert_eq!(cast.len(), 2);
+        assert_eq!(cast[0].id, 12898);
+        assert_eq!(cast[1].name, "Tom Hanks");
+
+        let directors = row.crew_with_job("Director");
+        assert_eq!(
+            directors,
+            vec![Credit {
+                id: 7879,
+                name: KString::from_static("John Lasseter")
+            }]
+        );
+        assert!(row.crew_with_job("Composer").is_empty());
+    }
+
+    #[test]
+    fn keywords_row() {
+        let row = KeywordsRowRaw {
+            id: "862".to_string(),
+            keywords: "[{'id': 931, 'name': 'jealousy'}, {'id': 4290, 'name': 'toy'}]".to_string(),
+        };
+        assert_eq!(row.id(), "862");
+        assert_eq!(row.keyword_ids(), HashSet::from([931, 4290]));
+        assert_eq!(row.keyword_names()[&4290], "toy");
+    }
+
+    fn parse_row(headers: &[&str], row: &[&str]) -> Result<MovieRowRaw, csv::Error> {
+        from_record(
+            &StringRecord::from(row.to_vec()),
+            &StringRecord::from(headers.to_vec()),
+        )
+    }
+
+    const HEADERS: [&str; 18] = [
+        "adult",
+        "belongs_to_collection",
+        "budget",
+        "genres",
+        "id",
+        "imdb_id",
+        "original_language",
+        "popularity",
+        "production_companies",
+        "production_countries",
+        "release_date",
+        "revenue",
+        "runtime",
+        "spoken_languages",
+        "status",
+        "title",
+        "vote_average",
+        "vote_count",
+    ];
+
+    #[test]
+    fn remaining_columns() {
+        // counts come both as integers and as floats depending on the export.
+        for vote_count in ["5415", "5415.0"] {
+            remaining_columns_with(vote_count);
+        }
+    }
+
+    fn remaining_columns_with(vote_count: &str) {
+        let raw = parse_row(
+            &HEADERS,
+            &[
+                "False",
+                "{'id': 10194, 'name': 'Toy Story Collection', 'poster_path': '/7G9915LfUQ2lVfwMEEhDsn3kT4B.jpg', 'backdrop_path': None}",
+                "30000000",
+                "[{'id': 16, 'name': 'Animation'}]",
+                "862",
+                "tt0114709",
+                "en",
+                "21.946943",
+                "[{'name': 'Pixar Animation Studios', 'id': 3}]",
+                "[{'iso_3166_1': 'US', 'name': 'United States of America'}]",
+                "1995-10-30",
+                "373554033",
+                "81.0",
+                "[{'iso_639_1': 'en', 'name': 'English'}, {'iso_639_1': 'fr', 'name': 'Français'}]",
+                "Released",
+                "Toy Story",
+                "7.7",
+                vote_count,
+            ],
+        )
+        .unwrap();
+        let movie = raw.to_movie(&None).unwrap();
+
+        assert_eq!(movie.title, "Toy Story");
+        assert_eq!(movie.imdb_id.as_deref(), Some("tt0114709"));
+        assert_eq!(movie.runtime, Some(81.0));
+        assert_eq!(movie.original_language.as_deref(), Some("en"));
+        assert_eq!(
+            movie.spoken_languages,
+            HashSet::from([KString::from_static("en"), KString::from_static("fr")])
+        );
+        assert_eq!(
+            movie.production_countries,
+            HashSet::from([KString::from_static("US")])
+        );
+        assert_eq!(
+            movie.collection,
+            Some(Collection {
+                id: 10194,
+                name: KString::from_static("Toy Story Collection")
+            })
+        );
+        assert_eq!(movie.vote_average, Some(7.7));
+        assert_eq!(movie.vote_count, Some(5415));
+        assert_eq!(movie.adult, Some(false));
+    }
+
+    #[test]
+    fn remaining_columns_blank_or_missing() {
+        let raw = parse_row(
+            &HEADERS,
+            &[
+                " - Written by Ørnås",
+                "",
+                "100",
+                "[]",
+                "1",
+                "",
+                "",
+                "1.0",
+                "[]",
+                "[]",
+                "1995-10-30",
+                "200",
+                "0.0",
+                "",
+                "Released",
+                "",
+                "0.0",
+                "0",
+            ],
+        )
+        .unwrap();
+        let movie = raw.to_movie(&None).unwrap();
+
+        assert_eq!(movie.title, "");
+        assert_eq!(movie.imdb_id, None);
+        assert_eq!(movie.runtime, None);
+        assert_eq!(movie.original_language, None);
+        assert!(movie.spoken_languages.is_empty());
+        assert_eq!(movie.collection, None);
+        assert_eq!(movie.vote_average, None);
+        assert_eq!(movie.vote_count, Some(0));
+        assert_eq!(movie.adult, None);
+
+        // a file with only the original columns still parses.
+        let raw = parse_row(
+            &[
+                "id",
+                "genres",
+                "production_companies",
+                "release_date",
+                "budget",
+                "revenue",
+                "popularity",
+                "status",
+            ],
+            &["1", "[]", "[]", "1995-10-30", "100", "200", "", "Released"],
+        )
+        .unwrap();
+        assert_eq!(raw.to_movie(&None).unwrap().runtime, None);
+    }
+
+    #[test]
+    fn filtered_rows() {
+        let headers = [
+            "id",
+            "genres",
+            "production_companies",
+            "release_date",
+            "budget",
+            "revenue",
+            "popularity",
+            "status",
+        ];
+        let row = |revenue: &str, status: &str| {
+            parse_row(
+                &headers,
+                &["1", "[]", "[]", "1995-10-30", "100", revenue, "", status],
+            )
+            .unwrap()
+        };
+        let last_run = NaiveDate::from_ymd_opt(1995, 1, 1);
+
+        assert_eq!(
+            row("0", "Released").to_movie(&None).err(),
+            Some(Filtered::NoRevenue)
+        );
+        assert_eq!(
+            row("200", "Rumored").to_movie(&None).err(),
+            Some(Filtered::NotReleased)
+        );
+        assert_eq!(
+            row("200", "Released").to_movie(&last_run).err(),
+            Some(Filtered::AfterLastRun)
+        );
+    }
+
+    #[test]
+    fn schema_check() {
+        let headers = StringRecord::from(vec![
+            "ID",
+            "genres",
+            "companies",
+            "release_date",
+            "budget",
+            "revenue",
+            "popularity",
+            "status",
+            "overview",
+            "rating",
+        ]);
+        let schema = check_schema(&headers).unwrap();
+        assert_eq!(schema.headers.get(0), Some("id"));
+        assert_eq!(schema.headers.get(2), Some("production_companies"));
+        assert_eq!(
+            schema.aliased,
+            vec![("companies".to_string(), "production_companies".to_string())]
+        );
+        assert_eq!(schema.unknown, vec!["rating".to_string()]);
+
+        // an alias is left alone when the real column is there too.
+        let headers = StringRecord::from(vec![
+            "id",
+            "genres",
+            "production_companies",
+            "release_date",
+            "budget",
+            "revenue",
+            "popularity",
+            "avg_popularity",
+            "status",
+        ]);
+        let schema = check_schema(&headers).unwrap();
+        assert!(schema.aliased.is_empty());
+        assert_eq!(schema.unknown, vec!["avg_popularity".to_string()]);
+
+        let e = check_schema(&StringRecord::from(vec!["id", "budget"])).unwrap_err();
+        assert_eq!(e.missing.len(), 6);
+        assert!(e.unknown.is_empty());
     }
 }
@@ -0,0 +1,250 @@
+use chrono::Datelike;
+use std::collections::HashMap;
+
+use crate::data::{Catalog, Movie, Reject, RejectReason};
+
+// years with fewer known values than this don't get z-scores; the spread is meaningless.
+const MIN_YEAR_SAMPLE: usize = 10;
+// TMDB's "entered in millions" values: 5 means $5,000,000.
+const MILLIONS: i64 = 1_000_000;
+// at or below this a budget or revenue is a placeholder rather than a figure in millions.
+const PLACEHOLDER: i64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Flag {
+    // known but below Heuristics::min_budget, e.g. 5 or a placeholder 1.
+    BudgetTooSmall,
+    RevenueTooSmall,
+    // revenue / budget outside [min_ratio, max_ratio].
+    RatioOutlier,
+    // log value more than max_z standard deviations from the release year's mean.
+    BudgetZScore,
+    RevenueZScore,
+}
+
+impl Flag {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BudgetTooSmall => "budget_too_small",
+            Self::RevenueTooSmall => "revenue_too_small",
+            Self::RatioOutlier => "ratio_outlier",
+            Self::BudgetZScore => "budget_z_score",
+            Self::RevenueZScore => "revenue_z_score",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // keep every movie as is, only list the flags.
+    Report,
+    // drop flagged movies.
+    Exclude,
+    // too-small budgets and revenues are scaled up from millions, or become unknown (0) when
+    // they're placeholders; anything still flagged afterwards is kept as is.
+    Correct,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name.to_lowercase().as_str() {
+            "report" => Some(Self::Report),
+            "exclude" => Some(Self::Exclude),
+            "correct" => Some(Self::Correct),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Report => "report",
+            Self::Exclude => "exclude",
+            Self::Correct => "correct",
+        }
+    }
+
+    // what happened to a movie under this mode, for the rejects report.
+    pub fn outcome(&self) -> &'static str {
+        match self {
+            Self::Report => "flagged",
+            Self::Exclude => "excluded",
+            Self::Correct => "corrected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heuristics {
+    pub min_budget: i64,
+    pub min_revenue: i64,
+    pub min_ratio: f64,
+    pub max_ratio: f64,
+    pub max_z: f64,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Heuristics {
+            min_budget: 1_000,
+            min_revenue: 1_000,
+            min_ratio: 0.001,
+            max_ratio: 1_000.0,
+            max_z: 3.0,
+        }
+    }
+}
+
+// flags per movie, in the same order as `movies`. budget 0 means unknown and is never flagged.
+pub fn check(movies: &[Movie], heuristics: &Heuristics) -> Vec<Vec<Flag>> {
+    let budget_stats = yearly_log_stats(movies, |x| x.budget);
+    let revenue_stats = yearly_log_stats(movies, |x| x.revenue);
+
+    movies
+        .iter()
+        .map(|movie| {
+            let mut flags = vec![];
+            let year = movie.release_date.year();
+
+            if movie.budget > 0 && movie.budget < heuristics.min_budget {
+                flags.push(Flag::BudgetTooSmall);
+            }
+            if movie.revenue > 0 && movie.revenue < heuristics.min_revenue {
+                flags.push(Flag::RevenueTooSmall);
+            }
+            if movie.budget > 0 && movie.revenue > 0 {
+                let ratio = movie.revenue as f64 / movie.budget as f64;
+                if ratio < heuristics.min_ratio || ratio > heuristics.max_ratio {
+                    flags.push(Flag::RatioOutlier);
+                }
+            }
+            if z_score(&budget_stats, year, movie.budget).is_some_and(|z| z > heuristics.max_z) {
+                flags.push(Flag::BudgetZScore);
+            }
+            if z_score(&revenue_stats, year, movie.revenue).is_some_and(|z| z > heuristics.max_z) {
+                flags.push(Flag::RevenueZScore);
+            }
+
+            flags
+        })
+        .collect()
+}
+
+// runs the checks and applies `mode`, recording every flagged movie in catalog.rejects.
+pub fn apply(catalog: &mut Catalog, heuristics: &Heuristics, mode: Mode) {
+    let flags = check(&catalog.movies, heuristics);
+    if mode == Mode::Correct {
+        return correct(catalog, heuristics, flags);
+    }
+    let movies = std::mem::take(&mut catalog.movies);
+
+    for (movie, flags) in movies.into_iter().zip(flags) {
+        if flags.is_empty() {
+            catalog.movies.push(movie);
+            continue;
+        }
+
+        catalog.rejects.push(quality_reject(&movie, flags, mode));
+        if mode == Mode::Report {
+            catalog.movies.push(movie);
+        }
+    }
+}
+
+// fixes what the flags say can be fixed, then checks again: a correction can clear other flags
+// (a budget in millions was also a ratio outlier) or leave some behind, and those are only
+// reported. the z-scores need every movie, so this runs over the whole catalog at once.
+fn correct(catalog: &mut Catalog, heuristics: &Heuristics, flags: Vec<Vec<Flag>>) {
+    for (movie, flags) in catalog.movies.iter_mut().zip(flags) {
+        let corrected: Vec<Flag> = flags
+            .into_iter()
+            .filter(|x| matches!(x, Flag::BudgetTooSmall | Flag::RevenueTooSmall))
+            .collect();
+        if corrected.is_empty() {
+            continue;
+        }
+
+        let budget = corrected.contains(&Flag::BudgetTooSmall);
+        let revenue = corrected.contains(&Flag::RevenueTooSmall);
+        if budget {
+            movie.budget = from_millions(movie.budget);
+        }
+        if revenue {
+            movie.revenue = from_millions(movie.revenue);
+        }
+        // if the scaled value is still way off the other one, it wasn't in millions either.
+        if movie.budget > 0 && movie.revenue > 0 {
+            let ratio = movie.revenue as f64 / movie.budget as f64;
+            if ratio < heuristics.min_ratio || ratio > heuristics.max_ratio {
+                if budget {
+                    movie.budget = 0;
+                }
+                if revenue {
+                    movie.revenue = 0;
+                }
+            }
+        }
+        movie.profit = movie.revenue - movie.budget;
+        catalog
+            .rejects
+            .push(quality_reject(movie, corrected, Mode::Correct));
+    }
+
+    let remaining = check(&catalog.movies, heuristics);
+    for (movie, flags) in catalog.movies.iter().zip(remaining) {
+        if !flags.is_empty() {
+            catalog
+                .rejects
+                .push(quality_reject(movie, flags, Mode::Report));
+        }
+    }
+}
+
+// 5 -> 5,000,000; placeholders become unknown.
+fn from_millions(value: i64) -> i64 {
+    if value <= PLACEHOLDER {
+        0
+    } else {
+        value.saturating_mul(MILLIONS)
+    }
+}
+
+fn quality_reject(movie: &Movie, flags: Vec<Flag>, action: Mode) -> Reject {
+    Reject {
+        movie_id: movie.id.clone(),
+        source: movie.source.clone(),
+        line: None,
+        reason: RejectReason::Quality { flags, action },
+    }
+}
+
+// (mean, standard deviation) of ln(value) per release year, over known values.
+fn yearly_log_stats(movies: &[Movie], value: impl Fn(&Movie) -> i64) -> HashMap<i32, (f64, f64)> {
+    let mut by_year: HashMap<i32, Vec<f64>> = HashMap::new();
+    movies
+        .iter()
+        .filter(|movie| value(movie) > 0)
+        .for_each(|movie| {
+            by_year
+                .entry(movie.release_date.year())
+                .or_default()
+                .push((value(movie) as f64).ln())
+        });
+
+    by_year
+        .into_iter()
+        .filter(|(_, values)| values.len() >= MIN_YEAR_SAMPLE)
+        .map(|(year, values)| {
+            let n = values.len() as f64;
+            let mean = values.iter().sum::<f64>() / n;
+            let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            (year, (mean, variance.sqrt()))
+        })
+        .collect()
+}
+
+fn z_score(stats: &HashMap<i32, (f64, f64)>, year: i32, value: i64) -> Option<f64> {
+    let (mean, std_dev) = stats.get(&year)?;
+    (value > 0 && *std_dev > 0.0).then(|| ((value as f64).ln() - mean).abs() / std_dev)
+}
+
+mod tests;
@@ -0,0 +1,127 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+    use chrono::NaiveDate;
+    use kstring::KString;
+
+    fn movie(id: &str, year: i32, budget: i64, revenue: i64) -> Movie {
+        Movie {
+            id: KString::from_ref(id),
+            release_date: NaiveDate::from_ymd_opt(year, 6, 1).unwrap(),
+            budget,
+            revenue,
+            profit: revenue - budget,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn thresholds_and_ratios() {
+        let movies = vec![
+            movie("1", 2000, 5, 20_000_000),
+            movie("2", 2000, 10_000_000, 1),
+            // unknown budget is fine.
+            movie("3", 2000, 0, 20_000_000),
+            movie("4", 2000, 10_000_000, 30_000_000),
+            movie("5", 2000, 10_000, 50_000_000),
+        ];
+        let flags = check(&movies, &Heuristics::default());
+        assert_eq!(flags[0], vec![Flag::BudgetTooSmall, Flag::RatioOutlier]);
+        assert_eq!(flags[1], vec![Flag::RevenueTooSmall, Flag::RatioOutlier]);
+        assert!(flags[2].is_empty());
+        assert!(flags[3].is_empty());
+        assert_eq!(flags[4], vec![Flag::RatioOutlier]);
+    }
+
+    #[test]
+    fn yearly_z_scores() {
+        let mut movies: Vec<Movie> = (0..20)
+            .map(|i| movie(&i.to_string(), 2000, 10_000_000 + i * 100_000, 30_000_000))
+            .collect();
+        movies.push(movie("huge", 2000, 9_000_000_000, 30_000_000));
+        // too few movies in 1990 to judge.
+        movies.push(movie("old", 1990, 9_000_000_000, 30_000_000));
+
+        let flags = check(&movies, &Heuristics::default());
+        assert!(flags[..20].iter().all(|x| x.is_empty()));
+        assert!(flags[20].contains(&Flag::BudgetZScore));
+        assert!(!flags[21].contains(&Flag::BudgetZScore));
+    }
+
+    #[test]
+    fn modes() {
+        let catalog = || Catalog {
+            movies: vec![
+                movie("1", 2000, 5, 20_000_000),
+                movie("2", 2000, 10_000_000, 30_000_000),
+            ],
+            ..Default::default()
+        };
+
+        let mut report = catalog();
+        apply(&mut report, &Heuristics::default(), Mode::Report);
+        assert_eq!(report.movies.len(), 2);
+        assert_eq!(report.movies[0].budget, 5);
+        assert_eq!(report.rejects.len(), 1);
+        assert_eq!(
+            report.rejects[0].reason,
+            RejectReason::Quality {
+                flags: vec![Flag::BudgetTooSmall, Flag::RatioOutlier],
+                action: Mode::Report
+            }
+        );
+
+        let mut exclude = catalog();
+        apply(&mut exclude, &Heuristics::default(), Mode::Exclude);
+        assert_eq!(exclude.movies.len(), 1);
+        assert_eq!(exclude.movies[0].id, "2");
+
+        let mut correct = Catalog {
+            movies: vec![
+                movie("1", 2000, 5, 20_000_000),
+                movie("2", 2000, 100_000_000, 5),
+                // placeholders, not millions.
+                movie("3", 2000, 100_000_000, 1),
+                movie("4", 2000, 1, 20_000_000),
+                // $2M against a $10B budget isn't believable either.
+                movie("5", 2000, 10_000_000_000, 2),
+                // nothing to correct, only flagged.
+                movie("6", 2000, 10_000, 50_000_000),
+            ],
+            ..Default::default()
+        };
+        apply(&mut correct, &Heuristics::default(), Mode::Correct);
+        assert_eq!(correct.movies.len(), 6);
+        // scaling the budget also clears the ratio flag.
+        assert_eq!(correct.movies[0].budget, 5_000_000);
+        assert_eq!(correct.movies[0].profit, 15_000_000);
+        assert_eq!(correct.movies[1].revenue, 5_000_000);
+        assert_eq!(correct.movies[2].revenue, 0);
+        assert_eq!(correct.movies[2].profit, -100_000_000);
+        assert_eq!(correct.movies[3].budget, 0);
+        assert_eq!(correct.movies[3].profit, 20_000_000);
+        assert_eq!(correct.movies[4].revenue, 0);
+        assert_eq!(correct.movies[5].budget, 10_000);
+        let rejects: Vec<_> = correct
+            .rejects
+            .iter()
+            .map(|x| (x.movie_id.as_str(), x.reason.describe()))
+            .collect();
+        let describe = |kind: &str, detail: &str| (kind.to_string(), detail.to_string());
+        assert_eq!(
+            rejects,
+            vec![
+                ("1", describe("quality_corrected", "budget_too_small")),
+                ("2", describe("quality_corrected", "revenue_too_small")),
+                ("3", describe("quality_corrected", "revenue_too_small")),
+                ("4", describe("quality_corrected", "budget_too_small")),
+                ("5", describe("quality_corrected", "revenue_too_small")),
+                ("6", describe("quality_flagged", "ratio_outlier")),
+            ]
+        );
+
+        assert_eq!(Mode::from_name("Exclude"), Some(Mode::Exclude));
+        assert_eq!(Mode::from_name("drop"), None);
+    }
+}
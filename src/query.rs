@@ -5,6 +5,10 @@ use std::{
     rc::Rc,
 };
 
+use crate::data::{Adjusted, Movie, RatingSummary, Returns};
+
+use self::sketch::Distributions;
+
 // use self::by_production_companies::prod_company_details;
 
 pub trait ById {
@@ -14,13 +18,112 @@ pub trait ById {
     // fn zero(&self) -> Box<Self>;
 }
 
+// the numbers every aggregate carries, regardless of what it's grouped by.
+pub trait Totals {
+    fn movie_count(&self) -> usize;
+    fn budget(&self) -> i64;
+    fn profit(&self) -> i64;
+    fn revenue(&self) -> i64;
+    fn avg_popularity(&self) -> f32;
+    fn ratings(&self) -> &RatingSummary;
+    // None unless the inflation stage ran.
+    fn adjusted(&self) -> Option<Adjusted>;
+    fn returns(&self) -> &Returns;
+    // only production companies keep these, and only when asked to.
+    fn distributions(&self) -> Option<&Distributions> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Movies,
+    Budget,
+    Profit,
+    Revenue,
+    Popularity,
+    // mean user rating, weighted by how many ratings each movie has.
+    Rating,
+    Ratings,
+    // constant dollars; zero when the inflation stage didn't run.
+    AdjustedBudget,
+    AdjustedProfit,
+    AdjustedRevenue,
+    // the next four only count movies with a known budget; None sorts as 0.
+    Roi,
+    MedianProfit,
+    HitRate,
+    Flops,
+}
+
+impl Metric {
+    pub fn from_name(name: &str) -> Option<Metric> {
+        match name.to_lowercase().as_str() {
+            "movies" => Some(Self::Movies),
+            "budget" => Some(Self::Budget),
+            "profit" => Some(Self::Profit),
+            "revenue" => Some(Self::Revenue),
+            "popularity" => Some(Self::Popularity),
+            "rating" => Some(Self::Rating),
+            "ratings" => Some(Self::Ratings),
+            "adjusted_budget" => Some(Self::AdjustedBudget),
+            "adjusted_profit" => Some(Self::AdjustedProfit),
+            "adjusted_revenue" => Some(Self::AdjustedRevenue),
+            "roi" => Some(Self::Roi),
+            "median_profit" => Some(Self::MedianProfit),
+            "hit_rate" => Some(Self::HitRate),
+            "flops" => Some(Self::Flops),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Movies => "movies",
+            Self::Budget => "budget",
+            Self::Profit => "profit",
+            Self::Revenue => "revenue",
+            Self::Popularity => "popularity",
+            Self::Rating => "rating",
+            Self::Ratings => "ratings",
+            Self::AdjustedBudget => "adjusted_budget",
+            Self::AdjustedProfit => "adjusted_profit",
+            Self::AdjustedRevenue => "adjusted_revenue",
+            Self::Roi => "roi",
+            Self::MedianProfit => "median_profit",
+            Self::HitRate => "hit_rate",
+            Self::Flops => "flops",
+        }
+    }
+
+    pub fn value<T: Totals>(&self, totals: &T) -> f64 {
+        match self {
+            Self::Movies => totals.movie_count() as f64,
+            Self::Budget => totals.budget() as f64,
+            Self::Profit => totals.profit() as f64,
+            Self::Revenue => totals.revenue() as f64,
+            Self::Popularity => totals.avg_popularity() as f64,
+            Self::Rating => totals.ratings().mean().unwrap_or(0.0),
+            Self::Ratings => totals.ratings().count as f64,
+            Self::AdjustedBudget => totals.adjusted().map(|x| x.budget).unwrap_or(0) as f64,
+            Self::AdjustedProfit => totals.adjusted().map(|x| x.profit).unwrap_or(0) as f64,
+            Self::AdjustedRevenue => totals.adjusted().map(|x| x.revenue).unwrap_or(0) as f64,
+            Self::Roi => totals.returns().roi().unwrap_or(0.0),
+            Self::MedianProfit => totals.returns().median_profit().unwrap_or(0.0),
+            Self::HitRate => totals.returns().hit_rate().unwrap_or(0.0),
+            Self::Flops => totals.returns().flops as f64,
+        }
+    }
+}
+
 pub type BucketYearMap<T> = BTreeMap<i32, [HashMap<i64, Rc<T>>; 12]>;
 pub type BucketYearMapFlattned<T> = BTreeMap<i32, Vec<Rc<T>>>;
+pub type BucketYearSummed<T> = BTreeMap<i32, HashMap<i64, Rc<T>>>;
 
 fn upsert_details<T: ById + Clone>(map: &mut HashMap<i64, Rc<T>>, detail: &Rc<T>) {
     map.entry(detail.id())
         .and_modify(|x| {
-            *x = x.sum(&detail);
+            *x = x.sum(detail);
         })
         .or_insert(detail.clone());
 }
@@ -46,7 +149,7 @@ pub fn add_detail<'a, T: ById + Clone>(
 
 pub fn flatten_bucket_year_map<T>(map: &BucketYearMap<T>) -> BucketYearMapFlattned<T> {
     map.iter()
-        .filter(|(year, months)| !months.iter().all(|x| x.is_empty()))
+        .filter(|(_year, months)| !months.iter().all(|x| x.is_empty()))
         .map(|(year, months)| {
             let flatten = months
                 .iter()
@@ -59,5 +162,87 @@ pub fn flatten_bucket_year_map<T>(map: &BucketYearMap<T>) -> BucketYearMapFlattn
         .collect()
 }
 
+// collapses the months of each year, summing the entries that share an id.
+pub fn sum_by_year<T: ById + Clone>(map: &BucketYearMap<T>) -> BucketYearSummed<T> {
+    flatten_bucket_year_map(map)
+        .into_iter()
+        .map(|(year, details)| {
+            let mut summed = HashMap::new();
+            details
+                .iter()
+                .for_each(|detail| upsert_details(&mut summed, detail));
+            (year, summed)
+        })
+        .collect()
+}
+
+// collapses the whole map, summing every entry that shares an id across all years.
+pub fn sum_all<T: ById + Clone>(map: &BucketYearMap<T>) -> HashMap<i64, Rc<T>> {
+    let mut summed = HashMap::new();
+    map.values()
+        .flat_map(|months| months.iter())
+        .flat_map(|month| month.values())
+        .for_each(|detail| upsert_details(&mut summed, detail));
+    summed
+}
+
+// yearly totals for a single entity, skipping years it had no releases.
+pub fn timeline<T: ById + Clone>(map: &BucketYearMap<T>, id: i64) -> BTreeMap<i32, Rc<T>> {
+    sum_by_year(map)
+        .into_iter()
+        .flat_map(|(year, mut by_id)| by_id.remove(&id).map(|x| (year, x)))
+        .collect()
+}
+
+// highest `limit` entities of a year by the given metric, best first.
+pub fn top<T: ById + Clone + Totals>(
+    map: &BucketYearMap<T>,
+    year: i32,
+    metric: Metric,
+    limit: usize,
+) -> Vec<Rc<T>> {
+    sum_by_year(map)
+        .remove(&year)
+        .map(|by_id| {
+            by_id
+                .into_values()
+                .sorted_by(|a, b| {
+                    metric
+                        .value(b.as_ref())
+                        .total_cmp(&metric.value(a.as_ref()))
+                        .then(a.id().cmp(&b.id()))
+                })
+                .take(limit)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn bucket_movies<T: ById + Clone>(
+    movies: &[Movie],
+    to_details: impl Fn(&Movie) -> Vec<Rc<T>>,
+) -> BucketYearMap<T> {
+    let mut acc = BTreeMap::new();
+    for detail in movies.iter().flat_map(to_details) {
+        add_detail(&mut acc, &detail);
+    }
+    acc
+}
+
+pub mod allocation;
+pub mod by_collection;
+pub mod by_country;
+pub mod by_genres;
+pub mod by_keywords;
+pub mod by_people;
 pub mod by_production_companies;
+pub mod coproduction;
+pub mod entity;
+pub mod forecast;
+pub mod genre_combinations;
+pub mod lifecycle;
+pub mod market_share;
+pub mod peers;
 mod query_test;
+pub mod seasonality;
+pub mod sketch;
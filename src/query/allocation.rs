@@ -0,0 +1,117 @@
+use serde::Deserialize;
+use std::{collections::HashMap, io::Read};
+
+use itertools::Itertools;
+
+use crate::data::Movie;
+
+#[derive(Debug, Deserialize)]
+struct WeightRowRaw {
+    company_id: i64,
+    weight: f64,
+}
+
+// how a movie's budget, revenue and profit are split between its production companies.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Allocation {
+    // every company gets the whole movie; sums across companies overcount the market.
+    Full,
+    Equal,
+    // the first listed company gets everything.
+    Lead,
+    // in proportion to each company's weight; companies missing from the table weigh 1.
+    Weighted(HashMap<i64, f64>),
+}
+
+impl Allocation {
+    // Weighted starts out empty; the table is read separately with read_weights.
+    pub fn from_name(name: &str) -> Option<Allocation> {
+        match name {
+            "full" => Some(Allocation::Full),
+            "equal" => Some(Allocation::Equal),
+            "lead" => Some(Allocation::Lead),
+            "weighted" => Some(Allocation::Weighted(HashMap::new())),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Allocation::Full => "full",
+            Allocation::Equal => "equal",
+            Allocation::Lead => "lead",
+            Allocation::Weighted(_) => "weighted",
+        }
+    }
+
+    // what a report header says about the company figures below it.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Allocation::Full => "full credit to every company",
+            Allocation::Equal => "split equally between companies",
+            Allocation::Lead => "credited to the lead company",
+            Allocation::Weighted(_) => "split by company weight",
+        }
+    }
+
+    // the fraction of the movie `id` is credited with; None when it isn't in the movie's shares.
+    pub fn share_of(&self, movie: &Movie, id: i64) -> Option<f64> {
+        self.shares(movie)
+            .into_iter()
+            .find(|(x, _)| *x == id)
+            .map(|(_, share)| share)
+    }
+
+    // each company on the movie with the fraction it's credited with, lowest id first. the
+    // fractions add up to 1 for everything but Full.
+    pub fn shares(&self, movie: &Movie) -> Vec<(i64, f64)> {
+        let companies: Vec<i64> = movie
+            .production_companies
+            .iter()
+            .copied()
+            .sorted()
+            .collect();
+        if companies.is_empty() {
+            return vec![];
+        }
+
+        match self {
+            Allocation::Full => companies.iter().map(|x| (*x, 1.0)).collect(),
+            Allocation::Equal => {
+                let share = 1.0 / companies.len() as f64;
+                companies.iter().map(|x| (*x, share)).collect()
+            }
+            // older exports don't know the lead, so the lowest id stands in for it.
+            Allocation::Lead => {
+                let lead = movie
+                    .lead_company
+                    .filter(|x| movie.production_companies.contains(x))
+                    .unwrap_or(companies[0]);
+                vec![(lead, 1.0)]
+            }
+            Allocation::Weighted(weights) => {
+                let weights: Vec<f64> = companies
+                    .iter()
+                    .map(|x| weights.get(x).copied().unwrap_or(1.0).max(0.0))
+                    .collect();
+                let total: f64 = weights.iter().sum();
+                if total <= 0.0 {
+                    return Allocation::Equal.shares(movie);
+                }
+                companies
+                    .into_iter()
+                    .zip(weights)
+                    .map(|(id, weight)| (id, weight / total))
+                    .collect()
+            }
+        }
+    }
+}
+
+// a `company_id,weight` csv.
+pub fn read_weights(reader: impl Read) -> Result<HashMap<i64, f64>, csv::Error> {
+    csv::Reader::from_reader(reader)
+        .deserialize::<WeightRowRaw>()
+        .map(|row| row.map(|x| (x.company_id, x.weight)))
+        .collect()
+}
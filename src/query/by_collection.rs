@@ -0,0 +1,181 @@
+use std::rc::Rc;
+
+use chrono::NaiveDate;
+use kstring::KString;
+
+use crate::data::{Adjusted, Movie, RatingSummary, Returns};
+
+use super::{ById, Totals};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Installment {
+    pub movie_id: KString,
+    pub title: KString,
+    pub release_date: NaiveDate,
+    pub revenue: i64,
+    pub profit: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CollectionDetails {
+    pub id: i64,
+    pub name: KString,
+    pub date: NaiveDate,
+    pub budget: i64,
+    pub profit: i64,
+    pub revenue: i64,
+    pub avg_populatarity: f32,
+    pub ratings: RatingSummary,
+    pub adjusted: Option<Adjusted>,
+    pub returns: Returns,
+    // release order.
+    pub installments: Vec<Installment>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FranchiseSummary {
+    pub id: i64,
+    pub name: KString,
+    pub installments: usize,
+    pub revenue: i64,
+    pub profit: i64,
+    pub first_release: NaiveDate,
+    pub last_release: NaiveDate,
+    // mean days between consecutive installments, None for a single movie.
+    pub avg_gap_days: Option<f64>,
+    // revenue change from each installment to the next, as a fraction of the previous one.
+    pub revenue_trend: Vec<Option<f64>>,
+}
+
+pub fn movie_to_collection_details(value: &Movie) -> Vec<Rc<CollectionDetails>> {
+    value
+        .collection
+        .iter()
+        .map(|collection| {
+            Rc::new(CollectionDetails {
+                id: collection.id,
+                name: collection.name.clone(),
+                date: value.release_date,
+                budget: value.budget,
+                profit: value.profit,
+                revenue: value.revenue,
+                avg_populatarity: value.avg_populatarity,
+                ratings: value.ratings.clone(),
+                adjusted: value.adjusted,
+                returns: Returns::of(value),
+                installments: vec![Installment {
+                    movie_id: value.id.clone(),
+                    title: value.title.clone(),
+                    release_date: value.release_date,
+                    revenue: value.revenue,
+                    profit: value.profit,
+                }],
+            })
+        })
+        .collect()
+}
+
+impl ById for CollectionDetails {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn date(&self) -> &NaiveDate {
+        &self.date
+    }
+
+    fn sum(&self, other: &Self) -> Rc<Self> {
+        let mut installments: Vec<Installment> = self
+            .installments
+            .iter()
+            .chain(other.installments.iter())
+            .cloned()
+            .collect();
+        installments.sort_by(|a, b| {
+            a.release_date
+                .cmp(&b.release_date)
+                .then(a.movie_id.cmp(&b.movie_id))
+        });
+        installments.dedup_by(|a, b| a.movie_id == b.movie_id);
+
+        Rc::new(CollectionDetails {
+            id: self.id,
+            name: self.name.clone(),
+            date: self.date,
+            budget: self.budget + other.budget,
+            profit: self.profit + other.profit,
+            revenue: self.revenue + other.revenue,
+            avg_populatarity: (self.avg_populatarity + other.avg_populatarity) / 2.0,
+            ratings: self.ratings.merge(&other.ratings),
+            adjusted: Adjusted::combine(&self.adjusted, &other.adjusted),
+            returns: self.returns.merge(&other.returns),
+            installments,
+        })
+    }
+}
+
+impl Totals for CollectionDetails {
+    fn movie_count(&self) -> usize {
+        self.installments.len()
+    }
+
+    fn budget(&self) -> i64 {
+        self.budget
+    }
+
+    fn profit(&self) -> i64 {
+        self.profit
+    }
+
+    fn revenue(&self) -> i64 {
+        self.revenue
+    }
+
+    fn avg_popularity(&self) -> f32 {
+        self.avg_populatarity
+    }
+
+    fn ratings(&self) -> &RatingSummary {
+        &self.ratings
+    }
+
+    fn adjusted(&self) -> Option<Adjusted> {
+        self.adjusted
+    }
+
+    fn returns(&self) -> &Returns {
+        &self.returns
+    }
+}
+
+// `details` should cover the whole franchise, e.g. from query::sum_all.
+pub fn franchise_summary(details: &CollectionDetails) -> Option<FranchiseSummary> {
+    let first = details.installments.first()?;
+    let last = details.installments.last()?;
+
+    let gaps: Vec<i64> = details
+        .installments
+        .windows(2)
+        .map(|pair| (pair[1].release_date - pair[0].release_date).num_days())
+        .collect();
+
+    Some(FranchiseSummary {
+        id: details.id,
+        name: details.name.clone(),
+        installments: details.installments.len(),
+        revenue: details.revenue,
+        profit: details.profit,
+        first_release: first.release_date,
+        last_release: last.release_date,
+        avg_gap_days: (!gaps.is_empty())
+            .then(|| gaps.iter().sum::<i64>() as f64 / gaps.len() as f64),
+        revenue_trend: details
+            .installments
+            .windows(2)
+            .map(|pair| {
+                (pair[0].revenue != 0)
+                    .then(|| (pair[1].revenue - pair[0].revenue) as f64 / pair[0].revenue as f64)
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,55 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::data::{country_id, Movie};
+
+use super::{bucket_movies, entity::EntityDetails, BucketYearMap};
+
+// ids used by country_split.
+pub const IN_COUNTRY: i64 = 1;
+pub const OUTSIDE_COUNTRY: i64 = 0;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Breakdown {
+    pub movies: usize,
+    pub revenue: i64,
+    pub profit: i64,
+}
+
+// keyed by data::country_id. co-productions count towards every country on them.
+pub fn movie_to_country_details(value: &Movie) -> Vec<Rc<EntityDetails>> {
+    value
+        .production_countries
+        .iter()
+        .map(|code| EntityDetails::new(country_id(code), value))
+        .collect()
+}
+
+// every movie lands in exactly one of IN_COUNTRY / OUTSIDE_COUNTRY, e.g. US vs. the rest.
+pub fn country_split(movies: &[Movie], code: &str) -> BucketYearMap<EntityDetails> {
+    bucket_movies(movies, |movie| {
+        let id = if movie.production_countries.iter().any(|x| x == code) {
+            IN_COUNTRY
+        } else {
+            OUTSIDE_COUNTRY
+        };
+        vec![EntityDetails::new(id, movie)]
+    })
+}
+
+// totals per (country_id, company id) over the whole dataset.
+pub fn country_company_breakdown(movies: &[Movie]) -> HashMap<(i64, i64), Breakdown> {
+    let mut breakdown: HashMap<(i64, i64), Breakdown> = HashMap::new();
+    for movie in movies {
+        for country in &movie.production_countries {
+            for company in &movie.production_companies {
+                let entry = breakdown
+                    .entry((country_id(country), *company))
+                    .or_default();
+                entry.movies += 1;
+                entry.revenue += movie.revenue;
+                entry.profit += movie.profit;
+            }
+        }
+    }
+    breakdown
+}
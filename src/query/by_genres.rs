@@ -0,0 +1,13 @@
+use std::rc::Rc;
+
+use crate::data::Movie;
+
+use super::entity::EntityDetails;
+
+pub fn movie_to_genre_details(value: &Movie) -> Vec<Rc<EntityDetails>> {
+    value
+        .genres
+        .iter()
+        .map(|genre| EntityDetails::new(*genre, value))
+        .collect()
+}
@@ -0,0 +1,85 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use crate::data::Movie;
+
+use super::{entity::EntityDetails, sum_by_year, timeline, BucketYearMap, Totals};
+
+#[derive(Debug, PartialEq)]
+pub struct KeywordYear {
+    pub movies: usize,
+    pub revenue: i64,
+}
+
+pub fn movie_to_keyword_details(value: &Movie) -> Vec<Rc<EntityDetails>> {
+    value
+        .keywords
+        .iter()
+        .map(|keyword| EntityDetails::new(*keyword, value))
+        .collect()
+}
+
+// count and revenue of the movies carrying the keyword, per year.
+pub fn keyword_trend(map: &BucketYearMap<EntityDetails>, id: i64) -> BTreeMap<i32, KeywordYear> {
+    timeline(map, id)
+        .into_iter()
+        .map(|(year, details)| {
+            (
+                year,
+                KeywordYear {
+                    movies: details.movie_count(),
+                    revenue: details.revenue(),
+                },
+            )
+        })
+        .collect()
+}
+
+// keywords whose yearly movie count grows fastest over the span of the map, as the least squares
+// slope (movies per year). years without a release count as zero, so one-offs don't look rising.
+pub fn rising_keywords(
+    map: &BucketYearMap<EntityDetails>,
+    min_movies: usize,
+    limit: usize,
+) -> Vec<(i64, f64)> {
+    let (first, last) = match (map.keys().next(), map.keys().last()) {
+        (Some(first), Some(last)) if first < last => (*first, *last),
+        _ => return vec![],
+    };
+
+    let mut counts: BTreeMap<i64, Vec<f64>> = BTreeMap::new();
+    let span = (last - first + 1) as usize;
+    for (year, by_id) in sum_by_year(map) {
+        for (id, details) in by_id {
+            counts.entry(id).or_insert_with(|| vec![0.0; span])[(year - first) as usize] =
+                details.movie_count() as f64;
+        }
+    }
+
+    let mut slopes: Vec<(i64, f64)> = counts
+        .into_iter()
+        .filter(|(_, yearly)| yearly.iter().sum::<f64>() >= min_movies as f64)
+        .map(|(id, yearly)| (id, slope(&yearly)))
+        .collect();
+    slopes.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    slopes.truncate(limit);
+    slopes
+}
+
+fn slope(ys: &[f64]) -> f64 {
+    let n = ys.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let (num, den) = ys
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(num, den), (x, y)| {
+            let dx = x as f64 - mean_x;
+            (num + dx * (y - mean_y), den + dx * dx)
+        });
+
+    if den == 0.0 {
+        0.0
+    } else {
+        num / den
+    }
+}
@@ -0,0 +1,25 @@
+use std::rc::Rc;
+
+use crate::data::Movie;
+
+use super::entity::EntityDetails;
+
+// only the leads get credited; the long tail of the cast list would drown them out.
+pub const TOP_BILLED_CAST: usize = 5;
+
+pub fn movie_to_director_details(value: &Movie) -> Vec<Rc<EntityDetails>> {
+    value
+        .directors
+        .iter()
+        .map(|director| EntityDetails::new(*director, value))
+        .collect()
+}
+
+pub fn movie_to_actor_details(value: &Movie) -> Vec<Rc<EntityDetails>> {
+    value
+        .cast
+        .iter()
+        .take(TOP_BILLED_CAST)
+        .map(|actor| EntityDetails::new(*actor, value))
+        .collect()
+}
@@ -1,42 +1,78 @@
-use std::{collections::HashSet, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use chrono::NaiveDate;
 use kstring::KString;
 
-use crate::data::Movie;
+use crate::data::{Adjusted, Movie, RatingSummary, Returns};
+
+use super::{allocation::Allocation, sketch::Distributions, Totals};
 
 #[derive(Debug, Clone)]
 pub struct ProdCompanyMetadata {
-    movieIds: HashSet<KString>,
-    genreIds: HashSet<i64>,
+    pub movie_ids: HashSet<KString>,
+    pub genre_ids: HashSet<i64>,
+    // movies per genre, for comparing genre mixes.
+    pub genre_counts: HashMap<i64, usize>,
 }
 #[derive(Debug, Clone)]
 pub struct ProdCompanyDetails {
-    id: i64,
-    date: NaiveDate, // does this make sense...
-    budget: i64,
-    profit: i64,
-    revenue: i64,
-    avg_populatarity: f32,
-    metadata: ProdCompanyMetadata,
+    pub id: i64,
+    pub date: NaiveDate, // does this make sense...
+    pub budget: i64,
+    pub profit: i64,
+    pub revenue: i64,
+    pub avg_populatarity: f32,
+    pub ratings: RatingSummary,
+    pub adjusted: Option<Adjusted>,
+    pub returns: Returns,
+    // only kept when asked for; see movie_to_details.
+    pub distributions: Option<Distributions>,
+    pub metadata: ProdCompanyMetadata,
 }
 
+// one entry per credited company, with the money scaled by its share of the movie.
+// `distributions` also keeps sketches of the per-movie revenue, profit and popularity.
 // impl From<&Movie> for Vec<ProdCompanyDetails> {
-pub fn movie_to_details(value: &Movie) -> Vec<Rc<ProdCompanyDetails>> {
-    value
-        .production_companies
-        .iter()
-        .map(|prod| {
+pub fn movie_to_details(
+    value: &Movie,
+    allocation: &Allocation,
+    distributions: bool,
+) -> Vec<Rc<ProdCompanyDetails>> {
+    allocation
+        .shares(value)
+        .into_iter()
+        .map(|(prod, share)| {
+            // just the money, so returns and sketches see the company's part of the movie.
+            let part = Movie {
+                budget: scale(value.budget, share),
+                revenue: scale(value.revenue, share),
+                profit: scale(value.profit, share),
+                avg_populatarity: value.avg_populatarity,
+                adjusted: value.adjusted.map(|x| Adjusted {
+                    budget: scale(x.budget, share),
+                    revenue: scale(x.revenue, share),
+                    profit: scale(x.profit, share),
+                }),
+                ..Default::default()
+            };
             Rc::new(ProdCompanyDetails {
-                id: *prod,
+                id: prod,
                 date: value.release_date,
-                budget: value.budget,
-                profit: value.profit,
-                revenue: value.revenue,
+                budget: part.budget,
+                profit: part.profit,
+                revenue: part.revenue,
                 avg_populatarity: value.avg_populatarity,
+                ratings: value.ratings.clone(),
+                adjusted: part.adjusted,
+                returns: Returns::of(&part),
+                distributions: distributions.then(|| Distributions::of(&part)),
                 metadata: ProdCompanyMetadata {
-                    movieIds: HashSet::from([value.id.clone(); 1]),
-                    genreIds: value.genres.clone(),
+                    movie_ids: HashSet::from([value.id.clone(); 1]),
+                    genre_ids: value.genres.clone(),
+                    genre_counts: value.genres.iter().map(|x| (*x, 1)).collect(),
                 },
             })
         })
@@ -44,6 +80,18 @@ pub fn movie_to_details(value: &Movie) -> Vec<Rc<ProdCompanyDetails>> {
 }
 // }
 
+fn merge_counts(a: &HashMap<i64, usize>, b: &HashMap<i64, usize>) -> HashMap<i64, usize> {
+    let mut merged = a.clone();
+    for (id, count) in b {
+        *merged.entry(*id).or_default() += count;
+    }
+    merged
+}
+
+pub fn scale(amount: i64, share: f64) -> i64 {
+    (amount as f64 * share).round() as i64
+}
+
 impl super::ById for ProdCompanyDetails {
     fn id(&self) -> i64 {
         self.id
@@ -56,28 +104,74 @@ impl super::ById for ProdCompanyDetails {
     fn sum(&self, other: &Self) -> Rc<Self> {
         let details = ProdCompanyDetails {
             id: self.id,
-            date: self.date.clone(),
+            date: self.date,
             budget: self.budget + other.budget,
             profit: self.profit + other.profit,
             revenue: self.revenue + other.revenue,
             avg_populatarity: (self.avg_populatarity + other.avg_populatarity) / 2.0,
+            ratings: self.ratings.merge(&other.ratings),
+            adjusted: Adjusted::combine(&self.adjusted, &other.adjusted),
+            returns: self.returns.merge(&other.returns),
+            distributions: Distributions::combine(&self.distributions, &other.distributions),
             // probably not the best performance... but immutable.
             metadata: ProdCompanyMetadata {
-                movieIds: self
+                movie_ids: self
                     .metadata
-                    .movieIds
-                    .union(&other.metadata.movieIds)
+                    .movie_ids
+                    .union(&other.metadata.movie_ids)
                     .cloned()
                     .collect(),
-                genreIds: self
+                genre_ids: self
                     .metadata
-                    .genreIds
-                    .union(&other.metadata.genreIds)
+                    .genre_ids
+                    .union(&other.metadata.genre_ids)
                     .copied()
                     .collect(),
+                genre_counts: merge_counts(
+                    &self.metadata.genre_counts,
+                    &other.metadata.genre_counts,
+                ),
             },
         };
 
         Rc::new(details)
     }
 }
+
+impl Totals for ProdCompanyDetails {
+    fn movie_count(&self) -> usize {
+        self.metadata.movie_ids.len()
+    }
+
+    fn budget(&self) -> i64 {
+        self.budget
+    }
+
+    fn profit(&self) -> i64 {
+        self.profit
+    }
+
+    fn revenue(&self) -> i64 {
+        self.revenue
+    }
+
+    fn avg_popularity(&self) -> f32 {
+        self.avg_populatarity
+    }
+
+    fn ratings(&self) -> &RatingSummary {
+        &self.ratings
+    }
+
+    fn adjusted(&self) -> Option<Adjusted> {
+        self.adjusted
+    }
+
+    fn returns(&self) -> &Returns {
+        &self.returns
+    }
+
+    fn distributions(&self) -> Option<&Distributions> {
+        self.distributions.as_ref()
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::Datelike;
+use itertools::Itertools;
+
+use crate::data::{Dictionary, Movie};
+
+// movies two companies made together. the pair is credited with the full revenue, like every
+// company is under Allocation::Full.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Partnership {
+    pub movies: usize,
+    pub revenue: i64,
+    pub by_year: BTreeMap<i32, usize>,
+}
+
+// companies as nodes and shared movies as weighted edges. edges are keyed with the lower id
+// first, so each pair is stored once.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoproductionGraph {
+    pub nodes: BTreeSet<i64>,
+    pub edges: BTreeMap<(i64, i64), Partnership>,
+}
+
+impl CoproductionGraph {
+    pub fn of(movies: &[Movie]) -> CoproductionGraph {
+        let mut graph = CoproductionGraph::default();
+        for movie in movies {
+            let companies = movie.production_companies.iter().copied().sorted();
+            graph.nodes.extend(companies.clone());
+            for (a, b) in companies.tuple_combinations() {
+                let edge = graph.edges.entry((a, b)).or_default();
+                edge.movies += 1;
+                edge.revenue += movie.revenue;
+                *edge.by_year.entry(movie.release_date.year()).or_default() += 1;
+            }
+        }
+        graph
+    }
+
+    // the pairs with the most shared movies.
+    pub fn top_pairs(&self, limit: usize) -> Vec<((i64, i64), &Partnership)> {
+        self.edges
+            .iter()
+            .sorted_by_key(|(pair, edge)| (std::cmp::Reverse(edge.movies), **pair))
+            .take(limit)
+            .map(|(pair, edge)| (*pair, edge))
+            .collect()
+    }
+
+    // everyone `id` made a movie with, most shared movies first.
+    pub fn partners(&self, id: i64) -> Vec<(i64, &Partnership)> {
+        self.edges
+            .iter()
+            .filter_map(|((a, b), edge)| {
+                if *a == id {
+                    Some((*b, edge))
+                } else if *b == id {
+                    Some((*a, edge))
+                } else {
+                    None
+                }
+            })
+            .sorted_by_key(|(partner, edge)| (std::cmp::Reverse(edge.movies), *partner))
+            .collect()
+    }
+
+    // per year, who `id` worked with and on how many movies.
+    pub fn partners_by_year(&self, id: i64) -> BTreeMap<i32, Vec<(i64, usize)>> {
+        let mut years: BTreeMap<i32, Vec<(i64, usize)>> = BTreeMap::new();
+        for (partner, edge) in self.partners(id) {
+            for (year, movies) in &edge.by_year {
+                years.entry(*year).or_default().push((partner, *movies));
+            }
+        }
+        years.values_mut().for_each(|x| {
+            x.sort_by_key(|(partner, movies)| (std::cmp::Reverse(*movies), *partner))
+        });
+        years
+    }
+
+    // groups of companies linked by any chain of co-productions, biggest first. companies that
+    // never co-produced are their own component.
+    pub fn components(&self) -> Vec<Vec<i64>> {
+        let mut parent: BTreeMap<i64, i64> = self.nodes.iter().map(|x| (*x, *x)).collect();
+        for (a, b) in self.edges.keys() {
+            let (a, b) = (root(&mut parent, *a), root(&mut parent, *b));
+            if a != b {
+                parent.insert(a.max(b), a.min(b));
+            }
+        }
+
+        let mut components: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+        for node in &self.nodes {
+            let root = root(&mut parent, *node);
+            components.entry(root).or_default().push(*node);
+        }
+        components
+            .into_values()
+            .sorted_by_key(|x| (std::cmp::Reverse(x.len()), x[0]))
+            .collect()
+    }
+
+    pub fn to_dot(&self, names: &Dictionary) -> String {
+        let mut out = String::from("graph coproduction {\n");
+        for node in &self.nodes {
+            out += &format!(
+                "  {node} [label=\"{}\"];\n",
+                dot_escape(&label(names, *node))
+            );
+        }
+        for ((a, b), edge) in &self.edges {
+            out += &format!("  {a} -- {b} [weight={0}, label=\"{0}\"];\n", edge.movies);
+        }
+        out += "}\n";
+        out
+    }
+
+    pub fn to_graphml(&self, names: &Dictionary) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n",
+            "  <key id=\"movies\" for=\"edge\" attr.name=\"movies\" attr.type=\"int\"/>\n",
+            "  <key id=\"revenue\" for=\"edge\" attr.name=\"revenue\" attr.type=\"long\"/>\n",
+            "  <graph id=\"coproduction\" edgedefault=\"undirected\">\n",
+        ));
+        for node in &self.nodes {
+            out += &format!(
+                "    <node id=\"{node}\"><data key=\"name\">{}</data></node>\n",
+                xml_escape(&label(names, *node))
+            );
+        }
+        for ((a, b), edge) in &self.edges {
+            out += &format!(
+                "    <edge source=\"{a}\" target=\"{b}\"><data key=\"movies\">{}</data><data key=\"revenue\">{}</data></edge>\n",
+                edge.movies, edge.revenue
+            );
+        }
+        out += "  </graph>\n</graphml>\n";
+        out
+    }
+}
+
+// union-find lookup, pointing everything on the way straight at the root.
+fn root(parent: &mut BTreeMap<i64, i64>, id: i64) -> i64 {
+    let mut top = id;
+    while parent[&top] != top {
+        top = parent[&top];
+    }
+    let mut node = id;
+    while node != top {
+        node = parent.insert(node, top).unwrap_or(top);
+    }
+    top
+}
+
+fn label(names: &Dictionary, id: i64) -> String {
+    names
+        .get(&id)
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| id.to_string())
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
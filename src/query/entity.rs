@@ -0,0 +1,99 @@
+use std::{collections::HashSet, rc::Rc};
+
+use chrono::NaiveDate;
+use kstring::KString;
+
+use crate::data::{Adjusted, Movie, RatingSummary, Returns};
+
+use super::Totals;
+
+// plain per-movie totals keyed by whatever the movie is grouped on (genre, director, ...).
+#[derive(Debug, Clone)]
+pub struct EntityDetails {
+    pub id: i64,
+    pub date: NaiveDate,
+    pub budget: i64,
+    pub profit: i64,
+    pub revenue: i64,
+    pub avg_populatarity: f32,
+    pub ratings: RatingSummary,
+    pub adjusted: Option<Adjusted>,
+    pub returns: Returns,
+    pub movie_ids: HashSet<KString>,
+}
+
+impl EntityDetails {
+    pub fn new(id: i64, value: &Movie) -> Rc<EntityDetails> {
+        Rc::new(EntityDetails {
+            id,
+            date: value.release_date,
+            budget: value.budget,
+            profit: value.profit,
+            revenue: value.revenue,
+            avg_populatarity: value.avg_populatarity,
+            ratings: value.ratings.clone(),
+            adjusted: value.adjusted,
+            returns: Returns::of(value),
+            movie_ids: HashSet::from([value.id.clone(); 1]),
+        })
+    }
+}
+
+impl super::ById for EntityDetails {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn date(&self) -> &NaiveDate {
+        &self.date
+    }
+
+    fn sum(&self, other: &Self) -> Rc<Self> {
+        Rc::new(EntityDetails {
+            id: self.id,
+            date: self.date,
+            budget: self.budget + other.budget,
+            profit: self.profit + other.profit,
+            revenue: self.revenue + other.revenue,
+            avg_populatarity: (self.avg_populatarity + other.avg_populatarity) / 2.0,
+            ratings: self.ratings.merge(&other.ratings),
+            adjusted: Adjusted::combine(&self.adjusted, &other.adjusted),
+            returns: self.returns.merge(&other.returns),
+            movie_ids: self.movie_ids.union(&other.movie_ids).cloned().collect(),
+        })
+    }
+}
+
+impl Totals for EntityDetails {
+    fn movie_count(&self) -> usize {
+        self.movie_ids.len()
+    }
+
+    fn budget(&self) -> i64 {
+        self.budget
+    }
+
+    fn profit(&self) -> i64 {
+        self.profit
+    }
+
+    fn revenue(&self) -> i64 {
+        self.revenue
+    }
+
+    fn avg_popularity(&self) -> f32 {
+        self.avg_populatarity
+    }
+
+    fn ratings(&self) -> &RatingSummary {
+        &self.ratings
+    }
+
+    fn adjusted(&self) -> Option<Adjusted> {
+        self.adjusted
+    }
+
+    fn returns(&self) -> &Returns {
+        &self.returns
+    }
+}
@@ -0,0 +1,218 @@
+use super::{BucketYearMap, ById, Totals};
+
+pub const SEASON: usize = 12;
+pub const DEFAULT_PERIODS: usize = SEASON;
+pub const DEFAULT_HOLDOUT_YEARS: usize = 1;
+// the most the server will project or hold out; beyond that a forecast means nothing anyway.
+pub const MAX_PERIODS: usize = 10 * SEASON;
+pub const MAX_HOLDOUT_YEARS: usize = 20;
+// smoothing for level, trend and season. fixed rather than fitted: most series are short and
+// noisy enough that fitting them would mostly fit the noise.
+const ALPHA: f64 = 0.3;
+const BETA: f64 = 0.1;
+const GAMMA: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Series {
+    Revenue,
+    Releases,
+}
+
+impl Series {
+    pub fn from_name(name: &str) -> Option<Series> {
+        match name {
+            "revenue" => Some(Series::Revenue),
+            "releases" => Some(Series::Releases),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Series::Revenue => "revenue",
+            Series::Releases => "releases",
+        }
+    }
+
+    fn value<T: Totals>(&self, totals: &T) -> f64 {
+        match self {
+            Series::Revenue => totals.revenue() as f64,
+            Series::Releases => totals.movie_count() as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+    // least squares line through the whole series.
+    Linear,
+    // additive level, trend and monthly season; needs two full years.
+    HoltWinters,
+}
+
+impl Model {
+    pub fn from_name(name: &str) -> Option<Model> {
+        match name {
+            "linear" => Some(Model::Linear),
+            "holt_winters" => Some(Model::HoltWinters),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Linear => "linear",
+            Model::HoltWinters => "holt_winters",
+        }
+    }
+
+    // the next `periods` values after `series`, never below 0. None if the series is too short.
+    pub fn forecast(&self, series: &[f64], periods: usize) -> Option<Vec<f64>> {
+        let forecast = match self {
+            Model::Linear => linear(series, periods)?,
+            Model::HoltWinters => holt_winters(series, periods)?,
+        };
+        Some(forecast.into_iter().map(|x| x.max(0.0)).collect())
+    }
+}
+
+// one entity's monthly values, with months it had nothing in as 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthlySeries {
+    // year and 1-based month of the first value.
+    pub start: (i32, u32),
+    pub values: Vec<f64>,
+}
+
+impl MonthlySeries {
+    // from the entity's first month with a release to its last. None if it has none.
+    pub fn of<T: ById + Totals>(
+        map: &BucketYearMap<T>,
+        id: i64,
+        series: Series,
+    ) -> Option<MonthlySeries> {
+        let mut values = vec![];
+        let mut start = None;
+        for (year, months) in map {
+            for (month, by_id) in months.iter().enumerate() {
+                match (by_id.get(&id), start) {
+                    (Some(details), _) => {
+                        start.get_or_insert((*year, month as u32 + 1));
+                        values.push(series.value(details.as_ref()));
+                    }
+                    (None, Some(_)) => values.push(0.0),
+                    (None, None) => {}
+                }
+            }
+            // years nobody released anything in aren't in the map at all.
+            if let (Some(_), Some(next)) = (start, map.range(year + 1..).next()) {
+                values.extend(std::iter::repeat_n(
+                    0.0,
+                    (next.0 - year - 1) as usize * SEASON,
+                ));
+            }
+        }
+
+        let trailing = values.iter().rev().take_while(|x| **x == 0.0).count();
+        values.truncate(values.len() - trailing);
+        Some(MonthlySeries {
+            start: start?,
+            values,
+        })
+    }
+
+    // year and 1-based month of the value `offset` months after the start; the first forecast is
+    // at offset values.len().
+    pub fn month_at(&self, offset: usize) -> (i32, u32) {
+        let months = self.start.0 * 12 + self.start.1 as i32 - 1 + offset as i32;
+        (months.div_euclid(12), months.rem_euclid(12) as u32 + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Errors {
+    pub mae: f64,
+    pub rmse: f64,
+    // skips months where the actual value was 0; None if they all were.
+    pub mape: Option<f64>,
+}
+
+impl Errors {
+    pub fn of(actual: &[f64], predicted: &[f64]) -> Option<Errors> {
+        let pairs: Vec<(f64, f64)> = actual
+            .iter()
+            .copied()
+            .zip(predicted.iter().copied())
+            .collect();
+        if pairs.is_empty() {
+            return None;
+        }
+        let n = pairs.len() as f64;
+        let mae = pairs.iter().map(|(a, p)| (a - p).abs()).sum::<f64>() / n;
+        let rmse = (pairs.iter().map(|(a, p)| (a - p).powi(2)).sum::<f64>() / n).sqrt();
+        let relative: Vec<f64> = pairs
+            .iter()
+            .filter(|(a, _)| *a != 0.0)
+            .map(|(a, p)| ((a - p) / a).abs())
+            .collect();
+        let mape =
+            (!relative.is_empty()).then(|| relative.iter().sum::<f64>() / relative.len() as f64);
+        Some(Errors { mae, rmse, mape })
+    }
+}
+
+// fits on everything but the last `holdout_years`, then scores the forecast of those. None if
+// what's left is too short for the model.
+pub fn backtest(series: &[f64], model: Model, holdout_years: usize) -> Option<Errors> {
+    let holdout = holdout_years.checked_mul(SEASON)?;
+    let fit = series.len().checked_sub(holdout)?;
+    let forecast = model.forecast(&series[..fit], holdout)?;
+    Errors::of(&series[fit..], &forecast)
+}
+
+fn linear(series: &[f64], periods: usize) -> Option<Vec<f64>> {
+    if series.len() < 2 {
+        return None;
+    }
+    let n = series.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = series.iter().sum::<f64>() / n;
+    let (mut top, mut bottom) = (0.0, 0.0);
+    for (x, y) in series.iter().enumerate() {
+        top += (x as f64 - mean_x) * (y - mean_y);
+        bottom += (x as f64 - mean_x).powi(2);
+    }
+    let slope = top / bottom;
+    let intercept = mean_y - slope * mean_x;
+    Some(
+        (series.len()..series.len() + periods)
+            .map(|x| intercept + slope * x as f64)
+            .collect(),
+    )
+}
+
+fn holt_winters(series: &[f64], periods: usize) -> Option<Vec<f64>> {
+    if series.len() < SEASON * 2 {
+        return None;
+    }
+    let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len() as f64;
+    let (first, second) = (mean(&series[..SEASON]), mean(&series[SEASON..SEASON * 2]));
+
+    let mut level = first;
+    let mut trend = (second - first) / SEASON as f64;
+    let mut season: Vec<f64> = series[..SEASON].iter().map(|x| x - first).collect();
+
+    for (t, y) in series.iter().enumerate() {
+        let s = season[t % SEASON];
+        let last_level = level;
+        level = ALPHA * (y - s) + (1.0 - ALPHA) * (level + trend);
+        trend = BETA * (level - last_level) + (1.0 - BETA) * trend;
+        season[t % SEASON] = GAMMA * (y - level) + (1.0 - GAMMA) * s;
+    }
+
+    Some(
+        (1..=periods)
+            .map(|h| level + trend * h as f64 + season[(series.len() + h - 1) % SEASON])
+            .collect(),
+    )
+}
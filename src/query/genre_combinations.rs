@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::Datelike;
+use itertools::Itertools;
+
+use crate::data::{Movie, Returns};
+
+// pairs and triples; more than that and hardly any combination repeats.
+pub const MAX_COMBINATION: usize = 3;
+
+// sorted genre ids.
+pub type Combination = Vec<i64>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CombinationStats {
+    pub movies: usize,
+    pub budget: i64,
+    pub revenue: i64,
+    pub returns: Returns,
+}
+
+impl CombinationStats {
+    fn add(&mut self, movie: &Movie) {
+        self.movies += 1;
+        self.budget += movie.budget;
+        self.revenue += movie.revenue;
+        self.returns = self.returns.merge(&Returns::of(movie));
+    }
+
+    // over movies with a known budget.
+    pub fn avg_budget(&self) -> Option<f64> {
+        let returns = &self.returns;
+        (returns.budgeted > 0).then(|| returns.budget as f64 / returns.budgeted as f64)
+    }
+
+    pub fn avg_revenue(&self) -> f64 {
+        self.revenue as f64 / self.movies as f64
+    }
+
+    pub fn roi(&self) -> Option<f64> {
+        self.returns.roi()
+    }
+}
+
+pub type CombinationsByDecade = BTreeMap<i32, HashMap<Combination, CombinationStats>>;
+
+// every combination of up to MAX_COMBINATION genres a movie has, per decade (1990, 2000, ...).
+// a movie tagged Action, Comedy and Drama counts towards Action on its own, Action+Comedy and
+// so on, so a single genre is every movie that has it.
+pub fn genre_combinations(movies: &[Movie]) -> CombinationsByDecade {
+    let mut decades: CombinationsByDecade = BTreeMap::new();
+    for movie in movies {
+        let decade = movie.release_date.year().div_euclid(10) * 10;
+        let genres: Vec<i64> = movie.genres.iter().copied().sorted().collect();
+        let combinations = decades.entry(decade).or_default();
+        for size in 1..=MAX_COMBINATION.min(genres.len()) {
+            for combination in genres.iter().copied().combinations(size) {
+                combinations.entry(combination).or_default().add(movie);
+            }
+        }
+    }
+    decades
+}
+
+// per decade, movies tagged with nothing but that one genre: what a combination is compared
+// against, since the single-genre entries in genre_combinations include every combination too.
+pub fn genres_alone(movies: &[Movie]) -> BTreeMap<i32, HashMap<i64, CombinationStats>> {
+    let mut decades: BTreeMap<i32, HashMap<i64, CombinationStats>> = BTreeMap::new();
+    for movie in movies {
+        if let Ok(genre) = movie.genres.iter().exactly_one() {
+            let decade = movie.release_date.year().div_euclid(10) * 10;
+            decades
+                .entry(decade)
+                .or_default()
+                .entry(*genre)
+                .or_default()
+                .add(movie);
+        }
+    }
+    decades
+}
+
+// the combinations of `size` genres seen on the most movies across every decade.
+pub fn co_occurrence(
+    decades: &CombinationsByDecade,
+    size: usize,
+    limit: usize,
+) -> Vec<(Combination, usize)> {
+    let mut counts: HashMap<&Combination, usize> = HashMap::new();
+    for (combination, stats) in decades.values().flatten() {
+        if combination.len() == size {
+            *counts.entry(combination).or_default() += stats.movies;
+        }
+    }
+    counts
+        .into_iter()
+        .sorted_by_key(|(combination, movies)| (std::cmp::Reverse(*movies), (*combination).clone()))
+        .take(limit)
+        .map(|(combination, movies)| (combination.clone(), movies))
+        .collect()
+}
+
+// one decade's combinations of `size` genres on at least `min_movies` movies, most movies first.
+pub fn top_in_decade(
+    combinations: &HashMap<Combination, CombinationStats>,
+    size: usize,
+    min_movies: usize,
+    limit: usize,
+) -> Vec<(&Combination, &CombinationStats)> {
+    combinations
+        .iter()
+        .filter(|(combination, stats)| combination.len() == size && stats.movies >= min_movies)
+        .sorted_by_key(|(combination, stats)| (std::cmp::Reverse(stats.movies), *combination))
+        .take(limit)
+        .collect()
+}
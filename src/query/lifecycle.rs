@@ -0,0 +1,62 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use super::{timeline, BucketYearMap, ById, Totals};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lifecycle<T> {
+    pub id: i64,
+    pub first_year: i32,
+    pub last_year: i32,
+    // years with at least one release.
+    pub active_years: usize,
+    // runs of years with no releases between the first and the last, inclusive.
+    pub gaps: Vec<(i32, i32)>,
+    // highest revenue; the earliest one on a tie.
+    pub peak_year: i32,
+    pub yearly: BTreeMap<i32, Rc<T>>,
+}
+
+impl<T: Totals> Lifecycle<T> {
+    pub fn span(&self) -> i32 {
+        self.last_year - self.first_year + 1
+    }
+
+    pub fn releases(&self, year: i32) -> usize {
+        self.yearly
+            .get(&year)
+            .map(|x| x.movie_count())
+            .unwrap_or_default()
+    }
+}
+
+// None when `id` has no releases in `map`.
+pub fn lifecycle<T: ById + Clone + Totals>(
+    map: &BucketYearMap<T>,
+    id: i64,
+) -> Option<Lifecycle<T>> {
+    let yearly = timeline(map, id);
+    let first_year = *yearly.keys().next()?;
+    let last_year = *yearly.keys().next_back()?;
+
+    let mut gaps = vec![];
+    for (year, next) in yearly.keys().zip(yearly.keys().skip(1)) {
+        if next - year > 1 {
+            gaps.push((year + 1, next - 1));
+        }
+    }
+    let peak_year = yearly
+        .iter()
+        .rev()
+        .max_by_key(|(_, x)| x.revenue())
+        .map(|(year, _)| *year)?;
+
+    Some(Lifecycle {
+        id,
+        first_year,
+        last_year,
+        active_years: yearly.len(),
+        gaps,
+        peak_year,
+        yearly,
+    })
+}
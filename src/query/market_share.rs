@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use super::{sum_by_year, BucketYearMap, ById, Totals};
+
+// how many of the biggest entities top_share covers.
+pub const CONCENTRATION_TOP: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Share {
+    pub id: i64,
+    pub revenue: i64,
+    pub releases: usize,
+    pub revenue_share: f64,
+    pub release_share: f64,
+    // change in revenue share since the previous period (0 share if it had no releases there).
+    // None in the first period. an entity that released something in the previous period but
+    // nothing in this one is listed with a 0 share and the whole drop as its shift.
+    pub shift: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodShare {
+    pub year: i32,
+    // summed over every entity, so a co-production counts once per company; shares add up to 1.
+    pub revenue: i64,
+    pub releases: usize,
+    // Herfindahl-Hirschman index on revenue share in percent: 0 (spread out) .. 10000 (monopoly).
+    pub hhi: f64,
+    pub top_share: f64,
+    // biggest revenue share first.
+    pub shares: Vec<Share>,
+}
+
+pub fn market_share<T: ById + Clone + Totals>(map: &BucketYearMap<T>) -> Vec<PeriodShare> {
+    let mut periods: Vec<PeriodShare> = Vec::new();
+    let mut previous: Option<HashMap<i64, f64>> = None;
+
+    for (year, summed) in sum_by_year(map) {
+        let revenue: i64 = summed.values().map(|x| x.revenue()).sum();
+        let releases: usize = summed.values().map(|x| x.movie_count()).sum();
+
+        let exits = previous
+            .iter()
+            .flatten()
+            .filter(|(id, _)| !summed.contains_key(id));
+        let exits: Vec<Share> = exits
+            .map(|(id, share)| Share {
+                id: *id,
+                revenue: 0,
+                releases: 0,
+                revenue_share: 0.0,
+                release_share: 0.0,
+                shift: Some(-share),
+            })
+            .collect();
+
+        let shares: Vec<Share> = summed
+            .values()
+            .map(|x| {
+                let revenue_share = ratio(x.revenue() as f64, revenue as f64);
+                let shift = previous
+                    .as_ref()
+                    .map(|p| revenue_share - p.get(&x.id()).copied().unwrap_or_default());
+                Share {
+                    id: x.id(),
+                    revenue: x.revenue(),
+                    releases: x.movie_count(),
+                    revenue_share,
+                    release_share: ratio(x.movie_count() as f64, releases as f64),
+                    shift,
+                }
+            })
+            .chain(exits)
+            .sorted_by(|a, b| {
+                b.revenue_share
+                    .total_cmp(&a.revenue_share)
+                    .then(a.id.cmp(&b.id))
+            })
+            .collect();
+
+        let hhi = shares
+            .iter()
+            .map(|x| (x.revenue_share * 100.0).powi(2))
+            .sum();
+        let top_share = shares
+            .iter()
+            .take(CONCENTRATION_TOP)
+            .map(|x| x.revenue_share)
+            .sum();
+
+        previous = Some(
+            shares
+                .iter()
+                .filter(|x| x.releases > 0)
+                .map(|x| (x.id, x.revenue_share))
+                .collect(),
+        );
+        periods.push(PeriodShare {
+            year,
+            revenue,
+            releases,
+            hhi,
+            top_share,
+            shares,
+        });
+    }
+
+    periods
+}
+
+fn ratio(part: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        part / total
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use super::{by_production_companies::ProdCompanyDetails, sum_all, BucketYearMap, Totals};
+
+// fewer movies than this and a company's genre mix and cadence say little.
+pub const MIN_MOVIES: usize = 3;
+// how much each part counts towards the overall score; they add up to 1.
+const GENRE_WEIGHT: f64 = 0.5;
+const BUDGET_WEIGHT: f64 = 0.25;
+const CADENCE_WEIGHT: f64 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GenreSimilarity {
+    // angle between the genre count vectors; ignores how many movies there are.
+    Cosine,
+    // weighted: sum of the smaller count over sum of the larger, per genre.
+    Jaccard,
+}
+
+impl GenreSimilarity {
+    pub fn from_name(name: &str) -> Option<GenreSimilarity> {
+        match name {
+            "cosine" => Some(GenreSimilarity::Cosine),
+            "jaccard" => Some(GenreSimilarity::Jaccard),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GenreSimilarity::Cosine => "cosine",
+            GenreSimilarity::Jaccard => "jaccard",
+        }
+    }
+
+    fn of(&self, a: &HashMap<i64, usize>, b: &HashMap<i64, usize>) -> f64 {
+        let genres = a.keys().chain(b.keys()).unique();
+        let pairs: Vec<(f64, f64)> = genres
+            .map(|x| {
+                let count = |m: &HashMap<i64, usize>| m.get(x).copied().unwrap_or_default() as f64;
+                (count(a), count(b))
+            })
+            .collect();
+
+        let (top, bottom) = match self {
+            GenreSimilarity::Cosine => {
+                let dot: f64 = pairs.iter().map(|(x, y)| x * y).sum();
+                let norm = |v: Vec<f64>| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+                let a = norm(pairs.iter().map(|x| x.0).collect());
+                let b = norm(pairs.iter().map(|x| x.1).collect());
+                (dot, a * b)
+            }
+            GenreSimilarity::Jaccard => (
+                pairs.iter().map(|(x, y)| x.min(*y)).sum(),
+                pairs.iter().map(|(x, y)| x.max(*y)).sum(),
+            ),
+        };
+        if bottom > 0.0 {
+            top / bottom
+        } else {
+            0.0
+        }
+    }
+}
+
+// what a company is compared on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompanyProfile {
+    pub id: i64,
+    pub movies: usize,
+    pub genre_counts: HashMap<i64, usize>,
+    // per movie with a known budget.
+    pub avg_budget: Option<f64>,
+    // movies per year between the first and last release.
+    pub cadence: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peer {
+    pub id: i64,
+    // weighted blend of the three below, 0 .. 1.
+    pub score: f64,
+    pub genre: f64,
+    pub budget: f64,
+    pub cadence: f64,
+}
+
+pub fn profiles(map: &BucketYearMap<ProdCompanyDetails>) -> HashMap<i64, CompanyProfile> {
+    let mut years: HashMap<i64, (i32, i32)> = HashMap::new();
+    for (year, months) in map {
+        for id in months.iter().flat_map(|x| x.keys()) {
+            let span = years.entry(*id).or_insert((*year, *year));
+            *span = (span.0.min(*year), span.1.max(*year));
+        }
+    }
+
+    sum_all(map)
+        .into_iter()
+        .map(|(id, details)| {
+            let (first, last) = years[&id];
+            let returns = details.returns();
+            let profile = CompanyProfile {
+                id,
+                movies: details.movie_count(),
+                genre_counts: details.metadata.genre_counts.clone(),
+                avg_budget: (returns.budgeted > 0)
+                    .then(|| returns.budget as f64 / returns.budgeted as f64),
+                cadence: details.movie_count() as f64 / (last - first + 1) as f64,
+            };
+            (id, profile)
+        })
+        .collect()
+}
+
+// the `limit` companies most like `id`, best first, among those with at least `min_movies`.
+// None if `id` isn't in the profiles.
+pub fn peers(
+    profiles: &HashMap<i64, CompanyProfile>,
+    id: i64,
+    similarity: GenreSimilarity,
+    min_movies: usize,
+    limit: usize,
+) -> Option<Vec<Peer>> {
+    let target = profiles.get(&id)?;
+
+    let peers = profiles
+        .values()
+        .filter(|x| x.id != id && x.movies >= min_movies)
+        .map(|x| {
+            let genre = similarity.of(&target.genre_counts, &x.genre_counts);
+            let budget = match (target.avg_budget, x.avg_budget) {
+                (Some(a), Some(b)) => closeness(a, b),
+                _ => 0.0,
+            };
+            let cadence = closeness(target.cadence, x.cadence);
+            Peer {
+                id: x.id,
+                score: genre * GENRE_WEIGHT + budget * BUDGET_WEIGHT + cadence * CADENCE_WEIGHT,
+                genre,
+                budget,
+                cadence,
+            }
+        })
+        .sorted_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)))
+        .take(limit)
+        .collect();
+
+    Some(peers)
+}
+
+// 1 for the same scale, 1/2 an order of magnitude apart, 1/3 two orders apart...
+fn closeness(a: f64, b: f64) -> f64 {
+    if a <= 0.0 || b <= 0.0 {
+        return 0.0;
+    }
+    1.0 / (1.0 + (a / b).log10().abs())
+}
@@ -1,11 +1,11 @@
 #[cfg(test)]
 mod query_tests {
     use super::super::*;
+    use crate::data::Movie;
     use chrono::NaiveDate;
     use rand::{rngs::ThreadRng, *};
     use std::{
         collections::{BTreeMap, HashMap},
-        hash::Hash,
         rc::Rc,
     };
     #[derive(Debug, Clone)]
@@ -40,7 +40,7 @@ mod query_tests {
         fn sum(&self, other: &Self) -> Rc<Self> {
             Rc::new(Dummy {
                 id: self.id,
-                date: self.date.clone(),
+                date: self.date,
                 profit: self.profit + other.profit,
             })
         }
@@ -51,7 +51,7 @@ mod query_tests {
         let mut map: BucketYearMap<Dummy> = BTreeMap::new();
         assert_eq!(map.len(), 0);
         // Add details for the year 2021, month 5
-        let detail1 = Dummy::new(1, NaiveDate::from_ymd(2021, 5, 1));
+        let detail1 = Dummy::new(1, NaiveDate::from_ymd_opt(2021, 5, 1).unwrap());
         add_detail(&mut map, &detail1);
         assert_eq!(map.len(), 1);
         assert_eq!(map.get(&2022), None);
@@ -59,14 +59,14 @@ mod query_tests {
         assert_eq!(map.get(&2021).unwrap()[4].get(&1).unwrap().id, detail1.id);
 
         // Add details for the year 2022, month 7
-        let detail2 = Dummy::new(2, NaiveDate::from_ymd(2022, 7, 1));
+        let detail2 = Dummy::new(2, NaiveDate::from_ymd_opt(2022, 7, 1).unwrap());
         add_detail(&mut map, &detail2);
         assert_eq!(map.len(), 2);
         assert_eq!(map.get(&2022).unwrap().len(), 12);
         assert_eq!(map.get(&2022).unwrap()[6].get(&2).unwrap().id, detail2.id);
 
         // Add details for an existing year and month
-        let detail3 = Dummy::new(3, NaiveDate::from_ymd(2022, 7, 1));
+        let detail3 = Dummy::new(3, NaiveDate::from_ymd_opt(2022, 7, 1).unwrap());
         add_detail(&mut map, &detail3);
         assert_eq!(map.len(), 2);
         assert_eq!(map.get(&2022).unwrap().len(), 12);
@@ -77,10 +77,10 @@ mod query_tests {
     fn sum_add_details() {
         let mut map: BucketYearMap<Dummy> = BTreeMap::new();
 
-        let detail1 = Dummy::new(1, NaiveDate::from_ymd(2021, 5, 1));
-        let detail2 = Dummy::new(1, NaiveDate::from_ymd(2021, 5, 4));
-        let detail4 = Dummy::new(2, NaiveDate::from_ymd(2021, 5, 1));
-        let detail3 = Dummy::new(1, NaiveDate::from_ymd(2021, 4, 1));
+        let detail1 = Dummy::new(1, NaiveDate::from_ymd_opt(2021, 5, 1).unwrap());
+        let detail2 = Dummy::new(1, NaiveDate::from_ymd_opt(2021, 5, 4).unwrap());
+        let detail4 = Dummy::new(2, NaiveDate::from_ymd_opt(2021, 5, 1).unwrap());
+        let detail3 = Dummy::new(1, NaiveDate::from_ymd_opt(2021, 4, 1).unwrap());
         add_detail(&mut map, &detail1);
         add_detail(&mut map, &detail2);
         add_detail(&mut map, &detail3);
@@ -99,10 +99,13 @@ mod query_tests {
         let flattened = flatten_bucket_year_map(&map);
         println!("{:?}", flattened);
 
-        let flattened_group = flattened.get(&2021).unwrap().iter().group_by(|x| x.id);
-        let flattened_group: HashMap<_, _> = flattened_group
+        let flattened_group: HashMap<_, _> = flattened
+            .get(&2021)
+            .unwrap()
+            .iter()
+            .into_group_map_by(|x| x.id)
             .into_iter()
-            .map(|(k, v)| (k, v.collect_vec().len()))
+            .map(|(k, v)| (k, v.len()))
             .collect();
         println!("{:?}", flattened_group);
         assert_eq!(flattened.get(&2021).unwrap().len(), 3);
@@ -115,11 +118,11 @@ mod query_tests {
         let mut map: BucketYearMap<Dummy> = BTreeMap::new();
 
         // Add details for the year 2021, month 5
-        let detail1 = Dummy::new(1, NaiveDate::from_ymd(2021, 5, 1));
+        let detail1 = Dummy::new(1, NaiveDate::from_ymd_opt(2021, 5, 1).unwrap());
         add_detail(&mut map, &detail1);
 
         // Add details for the year 2022, month 7
-        let detail2 = Dummy::new(2, NaiveDate::from_ymd(2022, 7, 1));
+        let detail2 = Dummy::new(2, NaiveDate::from_ymd_opt(2022, 7, 1).unwrap());
         add_detail(&mut map, &detail2);
 
         let flattened_map = flatten_bucket_year_map(&map);
@@ -135,28 +138,28 @@ mod query_tests {
         let mut map: BucketYearMap<Dummy> = BTreeMap::new();
 
         // Add details for the year 2021, month 5
-        let detail1 = Dummy::new(1, NaiveDate::from_ymd(2021, 5, 1));
+        let detail1 = Dummy::new(1, NaiveDate::from_ymd_opt(2021, 5, 1).unwrap());
         add_detail(&mut map, &detail1);
         assert_eq!(map.len(), 1);
         assert_eq!(map.get(&2021).unwrap().len(), 12);
         assert_eq!(map.get(&2021).unwrap()[4].get(&1).unwrap().id, detail1.id);
 
         // Add details for the year 2022, month 7
-        let detail2 = Dummy::new(2, NaiveDate::from_ymd(2022, 7, 1));
+        let detail2 = Dummy::new(2, NaiveDate::from_ymd_opt(2022, 7, 1).unwrap());
         add_detail(&mut map, &detail2);
         assert_eq!(map.len(), 2);
         assert_eq!(map.get(&2022).unwrap().len(), 12);
         assert_eq!(map.get(&2022).unwrap()[6].get(&2).unwrap().id, detail2.id);
 
         // Add details for an existing year and month
-        let detail3 = Dummy::new(3, NaiveDate::from_ymd(2022, 7, 1));
+        let detail3 = Dummy::new(3, NaiveDate::from_ymd_opt(2022, 7, 1).unwrap());
         add_detail(&mut map, &detail3);
         assert_eq!(map.len(), 2);
         assert_eq!(map.get(&2022).unwrap().len(), 12);
         assert_eq!(map.get(&2022).unwrap()[6].get(&3).unwrap().id, detail3.id);
 
         // Add details for a different month of an existing year
-        let detail4 = Dummy::new(4, NaiveDate::from_ymd(2022, 8, 1));
+        let detail4 = Dummy::new(4, NaiveDate::from_ymd_opt(2022, 8, 1).unwrap());
         add_detail(&mut map, &detail4);
         assert_eq!(map.len(), 2);
         assert_eq!(map.get(&2022).unwrap().len(), 12);
@@ -168,11 +171,11 @@ mod query_tests {
         let mut map: BucketYearMap<Dummy> = BTreeMap::new();
 
         // Add details for the year 2021, month 5
-        let detail1 = Dummy::new(1, NaiveDate::from_ymd(2021, 5, 1));
+        let detail1 = Dummy::new(1, NaiveDate::from_ymd_opt(2021, 5, 1).unwrap());
         add_detail(&mut map, &detail1);
 
         // Add details for the year 2022, month 7
-        let detail2 = Dummy::new(2, NaiveDate::from_ymd(2022, 7, 1));
+        let detail2 = Dummy::new(2, NaiveDate::from_ymd_opt(2022, 7, 1).unwrap());
         add_detail(&mut map, &detail2);
 
         let flattened_map = flatten_bucket_year_map(&map);
@@ -183,7 +186,7 @@ mod query_tests {
         assert_eq!(flattened_map.get(&2022).unwrap()[0].id, detail2.id);
 
         // Add details for an additional month in the year 2022
-        let detail3 = Dummy::new(3, NaiveDate::from_ymd(2022, 8, 1));
+        let detail3 = Dummy::new(3, NaiveDate::from_ymd_opt(2022, 8, 1).unwrap());
         add_detail(&mut map, &detail3);
 
         let flattened_map = flatten_bucket_year_map(&map);
@@ -195,7 +198,7 @@ mod query_tests {
         assert_eq!(flattened_map.get(&2022).unwrap()[1].id, detail3.id);
 
         // Add details for a different year
-        let detail4 = Dummy::new(4, NaiveDate::from_ymd(2023, 1, 1));
+        let detail4 = Dummy::new(4, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
         add_detail(&mut map, &detail4);
 
         let flattened_map = flatten_bucket_year_map(&map);
@@ -214,11 +217,11 @@ mod query_tests {
         let mut map: BucketYearMap<Dummy> = BTreeMap::new();
 
         // Add details for the year 2021, month 5
-        let detail1 = Dummy::new(1, NaiveDate::from_ymd(2021, 5, 1));
+        let detail1 = Dummy::new(1, NaiveDate::from_ymd_opt(2021, 5, 1).unwrap());
         add_detail(&mut map, &detail1);
 
         // Add details for the year 2022, month 7
-        let detail2 = Dummy::new(2, NaiveDate::from_ymd(2022, 7, 1));
+        let detail2 = Dummy::new(2, NaiveDate::from_ymd_opt(2022, 7, 1).unwrap());
         add_detail(&mut map, &detail2);
 
         let flattened_map = flatten_bucket_year_map(&map);
@@ -229,7 +232,7 @@ mod query_tests {
         assert_eq!(flattened_map.get(&2022).unwrap()[0].id, detail2.id);
 
         // Add details for an additional month in the year 2022
-        let detail3 = Dummy::new(3, NaiveDate::from_ymd(2022, 8, 1));
+        let detail3 = Dummy::new(3, NaiveDate::from_ymd_opt(2022, 8, 1).unwrap());
         add_detail(&mut map, &detail3);
 
         let flattened_map = flatten_bucket_year_map(&map);
@@ -241,7 +244,7 @@ mod query_tests {
         assert_eq!(flattened_map.get(&2022).unwrap()[1].id, detail3.id);
 
         // Add details for a different year
-        let detail4 = Dummy::new(4, NaiveDate::from_ymd(2023, 1, 1));
+        let detail4 = Dummy::new(4, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
         add_detail(&mut map, &detail4);
 
         let flattened_map = flatten_bucket_year_map(&map);
@@ -255,11 +258,11 @@ mod query_tests {
         assert_eq!(flattened_map.get(&2023).unwrap()[0].id, detail4.id);
 
         // Add details for the same year and month but different IDs
-        let detail5 = Dummy::new(5, NaiveDate::from_ymd(2022, 8, 1));
+        let detail5 = Dummy::new(5, NaiveDate::from_ymd_opt(2022, 8, 1).unwrap());
         add_detail(&mut map, &detail5);
 
         // Add details for the same year and month but same IDs
-        let detail6 = Dummy::new(3, NaiveDate::from_ymd(2022, 8, 1));
+        let detail6 = Dummy::new(3, NaiveDate::from_ymd_opt(2022, 8, 1).unwrap());
         add_detail(&mut map, &detail6);
 
         let flattened_map = flatten_bucket_year_map(&map);
@@ -283,4 +286,589 @@ mod query_tests {
             .any(|d| d.id == detail5.id));
         assert_eq!(flattened_map.get(&2023).unwrap()[0].id, detail4.id);
     }
+
+    fn keyword_movie(id: &str, year: i32, keywords: &[i64], revenue: i64) -> Movie {
+        Movie {
+            id: kstring::KString::from_ref(id),
+            release_date: NaiveDate::from_ymd_opt(year, 3, 1).unwrap(),
+            revenue,
+            keywords: keywords.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn keyword_trends() {
+        let movies = vec![
+            keyword_movie("1", 2000, &[1, 2], 10),
+            keyword_movie("2", 2001, &[1], 20),
+            keyword_movie("3", 2002, &[1, 2], 30),
+            keyword_movie("4", 2002, &[1], 40),
+            keyword_movie("5", 2002, &[3], 50),
+        ];
+        let map = bucket_movies(&movies, by_keywords::movie_to_keyword_details);
+
+        let trend = by_keywords::keyword_trend(&map, 1);
+        assert_eq!(trend.len(), 3);
+        assert_eq!(
+            trend.get(&2002),
+            Some(&by_keywords::KeywordYear {
+                movies: 2,
+                revenue: 70
+            })
+        );
+        assert_eq!(by_keywords::keyword_trend(&map, 2).get(&2001), None);
+
+        let rising = by_keywords::rising_keywords(&map, 2, 10);
+        assert_eq!(rising.iter().map(|x| x.0).collect_vec(), vec![1, 2]);
+        assert!((rising[0].1 - 0.5).abs() < 1e-9);
+        assert!(rising[1].1.abs() < 1e-9);
+    }
+
+    fn country_movie(
+        id: &str,
+        year: i32,
+        countries: &[&str],
+        companies: &[i64],
+        revenue: i64,
+    ) -> Movie {
+        Movie {
+            id: kstring::KString::from_ref(id),
+            release_date: NaiveDate::from_ymd_opt(year, 3, 1).unwrap(),
+            revenue,
+            profit: revenue / 2,
+            production_countries: countries
+                .iter()
+                .map(|x| kstring::KString::from_ref(x))
+                .collect(),
+            production_companies: companies.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn by_country() {
+        let movies = vec![
+            country_movie("1", 2000, &["US", "GB"], &[7], 100),
+            country_movie("2", 2000, &["FR"], &[7, 8], 40),
+            country_movie("3", 2001, &["US"], &[8], 10),
+        ];
+
+        let map = bucket_movies(&movies, by_country::movie_to_country_details);
+        let us = timeline(&map, crate::data::country_id("US"));
+        assert_eq!(us.len(), 2);
+        assert_eq!(us[&2000].revenue, 100);
+
+        let split = sum_by_year(&by_country::country_split(&movies, "US"));
+        assert_eq!(split[&2000][&by_country::IN_COUNTRY].revenue, 100);
+        assert_eq!(split[&2000][&by_country::OUTSIDE_COUNTRY].revenue, 40);
+        assert!(!split[&2001].contains_key(&by_country::OUTSIDE_COUNTRY));
+
+        let breakdown = by_country::country_company_breakdown(&movies);
+        assert_eq!(breakdown.len(), 5);
+        assert_eq!(
+            breakdown[&(crate::data::country_id("US"), 8)],
+            by_country::Breakdown {
+                movies: 1,
+                revenue: 10,
+                profit: 5
+            }
+        );
+    }
+
+    fn installment(id: &str, date: NaiveDate, revenue: i64) -> Movie {
+        Movie {
+            id: kstring::KString::from_ref(id),
+            title: kstring::KString::from_ref(id),
+            release_date: date,
+            revenue,
+            profit: revenue - 10,
+            collection: Some(crate::data::Collection {
+                id: 10194,
+                name: kstring::KString::from_static("Toy Story Collection"),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn franchises() {
+        let movies = vec![
+            installment("3", NaiveDate::from_ymd_opt(2010, 6, 16).unwrap(), 150),
+            installment("1", NaiveDate::from_ymd_opt(1995, 10, 30).unwrap(), 100),
+            installment("2", NaiveDate::from_ymd_opt(1999, 10, 30).unwrap(), 200),
+            Movie {
+                id: kstring::KString::from_static("4"),
+                release_date: NaiveDate::from_ymd_opt(1999, 1, 1).unwrap(),
+                revenue: 1000,
+                ..Default::default()
+            },
+        ];
+
+        let map = bucket_movies(&movies, by_collection::movie_to_collection_details);
+        let summed = sum_all(&map);
+        assert_eq!(summed.len(), 1);
+
+        let summary = by_collection::franchise_summary(&summed[&10194]).unwrap();
+        assert_eq!(summary.installments, 3);
+        assert_eq!(summary.revenue, 450);
+        assert_eq!(summary.profit, 420);
+        assert_eq!(summary.first_release, movies[1].release_date);
+        assert_eq!(summary.last_release, movies[0].release_date);
+        let days = (movies[0].release_date - movies[1].release_date).num_days() as f64;
+        assert_eq!(summary.avg_gap_days, Some(days / 2.0));
+        assert_eq!(summary.revenue_trend, vec![Some(1.0), Some(-0.25)]);
+    }
+
+    fn full_credit(movie: &Movie) -> Vec<Rc<by_production_companies::ProdCompanyDetails>> {
+        by_production_companies::movie_to_details(movie, &allocation::Allocation::Full, false)
+    }
+
+    fn company_movie(id: &str, companies: &[i64], budget: i64, revenue: i64) -> Movie {
+        Movie {
+            id: kstring::KString::from_ref(id),
+            production_companies: companies.iter().copied().collect(),
+            release_date: NaiveDate::from_ymd_opt(2000, 5, 1).unwrap(),
+            budget,
+            revenue,
+            profit: revenue - budget,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn returns_metrics() {
+        let movies = vec![
+            // a hit, a flop and a movie with an unknown budget for company 1.
+            company_movie("1", &[1, 2], 100, 300),
+            company_movie("2", &[1], 100, 50),
+            company_movie("3", &[1], 0, 1000),
+            company_movie("4", &[2], 100, 150),
+        ];
+        let map = bucket_movies(&movies, full_credit);
+        let summed = sum_all(&map);
+
+        let returns = summed[&1].returns();
+        assert_eq!(returns.budgeted, 2);
+        assert_eq!(returns.roi(), Some(150.0 / 200.0));
+        assert_eq!(returns.hit_rate(), Some(0.5));
+        assert_eq!(returns.flops, 1);
+        assert_eq!(returns.median_profit(), Some((200.0 - 50.0) / 2.0));
+
+        let ranked = top(&map, 2000, Metric::Roi, 2);
+        assert_eq!(ranked.iter().map(|x| x.id).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(Metric::from_name("hit_rate"), Some(Metric::HitRate));
+    }
+
+    #[test]
+    fn sketch_quantiles_merge() {
+        use sketch::Sketch;
+
+        let mut low = Sketch::default();
+        let mut high = Sketch::default();
+        (1..=500).for_each(|x| low.add(x as f64 * 1000.0));
+        (501..=1000).for_each(|x| high.add(x as f64 * 1000.0));
+        let merged = low.merge(&high);
+
+        assert_eq!(merged.count(), 1000);
+        let close = |actual: Option<f64>, expected: f64| {
+            (actual.unwrap() - expected).abs() <= expected * 0.01
+        };
+        assert!(close(merged.quantile(0.5), 500_000.0));
+        assert!(close(merged.quantile(0.9), 900_000.0));
+        assert!(close(merged.quantile(0.99), 990_000.0));
+        assert_eq!(merged.quantile(0.0), Some(1000.0));
+        assert_eq!(merged.quantile(1.0), Some(1_000_000.0));
+        assert_eq!(Sketch::default().quantile(0.5), None);
+        assert!(close(merged.median(), 500_500.0));
+        assert_eq!(Sketch::of(7.0).merge(&Sketch::of(3.0)).median(), Some(5.0));
+        assert_eq!(Sketch::default().median(), None);
+
+        // losses, zero and gains, lowest bin first.
+        let mut profits = Sketch::default();
+        [-5000.0, -50.0, 0.0, 20.0, 30.0]
+            .iter()
+            .for_each(|x| profits.add(*x));
+        assert_eq!(
+            profits.histogram(),
+            vec![
+                (-10000.0, -1000.0, 1),
+                (-100.0, -10.0, 1),
+                (0.0, 0.0, 1),
+                (10.0, 100.0, 2)
+            ]
+        );
+        assert!(profits.quantile(0.0).unwrap() == -5000.0);
+        assert!(close(profits.quantile(0.25).map(|x| -x), 50.0));
+    }
+
+    #[test]
+    fn company_distributions() {
+        let movies = vec![
+            company_movie("1", &[1], 100, 300),
+            company_movie("2", &[1], 100, 50),
+        ];
+        let plain = sum_all(&bucket_movies(&movies, full_credit));
+        assert!(plain[&1].distributions().is_none());
+
+        let map = bucket_movies(&movies, |x| {
+            by_production_companies::movie_to_details(x, &allocation::Allocation::Full, true)
+        });
+        let summed = sum_all(&map);
+        let distributions = summed[&1].distributions().unwrap();
+        assert_eq!(distributions.revenue.count(), 2);
+        assert_eq!(distributions.profit.quantile(0.0), Some(-50.0));
+    }
+
+    #[test]
+    fn seasonality_against_market() {
+        let dated = |id: &str, companies: &[i64], month: u32, revenue: i64| Movie {
+            release_date: NaiveDate::from_ymd_opt(2000 + month as i32, month, 1).unwrap(),
+            ..company_movie(id, companies, 10, revenue)
+        };
+        let movies = vec![
+            dated("1", &[1], 6, 300),
+            dated("2", &[1], 6, 100),
+            dated("3", &[1], 12, 50),
+            dated("4", &[2], 12, 550),
+        ];
+        let map = bucket_movies(&movies, full_credit);
+        let market = seasonality::MonthTotals::of_movies(&movies);
+        assert_eq!(market.releases[5], 2);
+        assert_eq!(market.revenue[11], 600);
+
+        let company = seasonality::seasonality(&map, 1, &market).unwrap();
+        assert_eq!(company.totals.releases[5], 2);
+        assert!((company.release_share[5] - 2.0 / 3.0).abs() < 1e-9);
+        // 2/3 of its releases in june against half of the market's.
+        assert!((company.release_index[5].unwrap() - 4.0 / 3.0).abs() < 1e-9);
+        assert_eq!(company.release_index[0], None);
+        assert_eq!(company.best_month, Some(6));
+        assert_eq!(company.worst_month, Some(12));
+
+        assert!(seasonality::seasonality(&map, 99, &market).is_none());
+    }
+
+    #[test]
+    fn market_share_per_year() {
+        let dated = |id: &str, companies: &[i64], year: i32, revenue: i64| Movie {
+            release_date: NaiveDate::from_ymd_opt(year, 5, 1).unwrap(),
+            ..company_movie(id, companies, 10, revenue)
+        };
+        let movies = vec![
+            dated("1", &[1], 2000, 300),
+            dated("2", &[2], 2000, 100),
+            dated("3", &[1], 2001, 100),
+            dated("4", &[2], 2001, 100),
+            dated("5", &[3], 2001, 200),
+            dated("6", &[3], 2002, 50),
+            dated("7", &[3], 2003, 50),
+        ];
+        let map = bucket_movies(&movies, full_credit);
+        let periods = market_share::market_share(&map);
+        assert_eq!(periods.len(), 4);
+
+        let first = &periods[0];
+        assert_eq!(first.revenue, 400);
+        assert_eq!(first.shares[0].id, 1);
+        assert_eq!(first.shares[0].revenue_share, 0.75);
+        assert_eq!(first.shares[1].release_share, 0.5);
+        assert_eq!(first.shares[0].shift, None);
+        // 75^2 + 25^2
+        assert!((first.hhi - 6250.0).abs() < 1e-9);
+        assert_eq!(first.top_share, 1.0);
+
+        let second = &periods[1];
+        assert_eq!(second.shares[0].id, 3);
+        assert_eq!(second.shares[0].shift, Some(0.5));
+        let company_1 = second.shares.iter().find(|x| x.id == 1).unwrap();
+        assert_eq!(company_1.shift, Some(-0.5));
+        assert!((second.hhi - 3750.0).abs() < 1e-9);
+
+        // 1 and 2 released nothing in 2002, so they drop to 0 share, and only that once.
+        let third = &periods[2];
+        assert_eq!(third.shares.len(), 3);
+        assert_eq!(third.shares[0].shift, Some(0.5));
+        let company_2 = third.shares.iter().find(|x| x.id == 2).unwrap();
+        assert_eq!(company_2.releases, 0);
+        assert_eq!(company_2.revenue_share, 0.0);
+        assert_eq!(company_2.shift, Some(-0.25));
+        assert_eq!(third.top_share, 1.0);
+        assert_eq!(periods[3].shares.len(), 1);
+    }
+
+    #[test]
+    fn coproduction_graph() {
+        let movies = vec![
+            company_movie("1", &[1, 2], 10, 100),
+            company_movie("2", &[2, 1], 10, 50),
+            company_movie("3", &[2, 3, 4], 10, 10),
+            company_movie("4", &[5, 6], 10, 10),
+            company_movie("5", &[7], 10, 10),
+        ];
+        let graph = coproduction::CoproductionGraph::of(&movies);
+        assert_eq!(graph.nodes.len(), 7);
+        assert_eq!(graph.edges.len(), 5);
+
+        let top = graph.top_pairs(1);
+        assert_eq!(top[0].0, (1, 2));
+        assert_eq!(top[0].1.movies, 2);
+        assert_eq!(top[0].1.revenue, 150);
+
+        let partners: Vec<_> = graph
+            .partners(2)
+            .iter()
+            .map(|x| (x.0, x.1.movies))
+            .collect();
+        assert_eq!(partners, vec![(1, 2), (3, 1), (4, 1)]);
+        assert_eq!(graph.partners_by_year(3)[&2000], vec![(2, 1), (4, 1)]);
+
+        assert_eq!(
+            graph.components(),
+            vec![vec![1, 2, 3, 4], vec![5, 6], vec![7]]
+        );
+
+        let dot = graph.to_dot(&BTreeMap::from([(
+            1,
+            kstring::KString::from_static("A \"B\""),
+        )]));
+        assert!(dot.contains("1 [label=\"A \\\"B\\\"\"];"));
+        assert!(dot.contains("1 -- 2 [weight=2, label=\"2\"];"));
+        let graphml =
+            graph.to_graphml(&BTreeMap::from([(5, kstring::KString::from_static("R&D"))]));
+        assert!(graphml.contains("<data key=\"name\">R&amp;D</data>"));
+        assert!(graphml.contains("<edge source=\"1\" target=\"2\"><data key=\"movies\">2</data>"));
+    }
+
+    #[test]
+    fn allocation_strategies() {
+        use allocation::Allocation;
+
+        let movie = Movie {
+            lead_company: Some(2),
+            ..company_movie("1", &[1, 2, 3], 300, 900)
+        };
+        assert_eq!(
+            Allocation::Full.shares(&movie),
+            vec![(1, 1.0), (2, 1.0), (3, 1.0)]
+        );
+        assert_eq!(Allocation::Lead.shares(&movie), vec![(2, 1.0)]);
+        // no lead known, e.g. loaded from an older export.
+        let unknown_lead = company_movie("2", &[1, 2, 3], 300, 900);
+        assert_eq!(Allocation::Lead.shares(&unknown_lead), vec![(1, 1.0)]);
+        assert_eq!(Allocation::Lead.share_of(&movie, 2), Some(1.0));
+        assert_eq!(Allocation::Lead.share_of(&movie, 1), None);
+        assert_eq!(Allocation::Full.share_of(&movie, 1), Some(1.0));
+        let weighted = Allocation::Weighted(HashMap::from([(1, 2.0), (3, 0.0)]));
+        assert_eq!(
+            weighted.shares(&movie),
+            vec![(1, 2.0 / 3.0), (2, 1.0 / 3.0), (3, 0.0)]
+        );
+
+        let equal: Vec<_> =
+            by_production_companies::movie_to_details(&movie, &Allocation::Equal, true);
+        assert_eq!(equal.len(), 3);
+        assert_eq!(equal[0].budget, 100);
+        assert_eq!(equal[0].revenue, 300);
+        assert_eq!(equal[0].profit, 200);
+        assert_eq!(equal[0].returns.roi(), Some(2.0));
+        assert_eq!(equal[0].movie_count(), 1);
+        assert_eq!(
+            equal[0]
+                .distributions
+                .as_ref()
+                .unwrap()
+                .revenue
+                .quantile(1.0),
+            Some(300.0)
+        );
+        // the split adds back up to the movie, unlike full credit.
+        assert_eq!(equal.iter().map(|x| x.revenue).sum::<i64>(), 900);
+
+        let weights = allocation::read_weights("company_id,weight\n1,2.5\n7,1\n".as_bytes());
+        assert_eq!(weights.unwrap(), HashMap::from([(1, 2.5), (7, 1.0)]));
+        assert_eq!(Allocation::from_name("lead"), Some(Allocation::Lead));
+        assert_eq!(Allocation::from_name("most"), None);
+    }
+
+    #[test]
+    fn peer_finder() {
+        use peers::GenreSimilarity;
+
+        let genre_movie = |id: &str, company: i64, year: i32, genres: &[i64], budget: i64| Movie {
+            genres: genres.iter().copied().collect(),
+            release_date: NaiveDate::from_ymd_opt(year, 5, 1).unwrap(),
+            ..company_movie(id, &[company], budget, budget * 2)
+        };
+        let movies = vec![
+            // 1 and 2 make the same kind of movies at the same scale and pace; 3 doesn't.
+            genre_movie("1", 1, 2000, &[28, 12], 100),
+            genre_movie("2", 1, 2001, &[28], 100),
+            genre_movie("3", 2, 2000, &[28, 12], 120),
+            genre_movie("4", 2, 2001, &[28], 80),
+            genre_movie("5", 3, 2000, &[35], 10000),
+        ];
+        let map = bucket_movies(&movies, full_credit);
+        let profiles = peers::profiles(&map);
+        assert_eq!(profiles[&1].genre_counts, HashMap::from([(28, 2), (12, 1)]));
+        assert_eq!(profiles[&1].avg_budget, Some(100.0));
+        assert_eq!(profiles[&1].cadence, 1.0);
+
+        let found = peers::peers(&profiles, 1, GenreSimilarity::Cosine, 1, 5).unwrap();
+        assert_eq!(found.iter().map(|x| x.id).collect::<Vec<_>>(), vec![2, 3]);
+        assert!((found[0].genre - 1.0).abs() < 1e-9);
+        assert_eq!(found[0].budget, 1.0);
+        assert_eq!(found[1].genre, 0.0);
+        // two orders of magnitude apart.
+        assert!((found[1].budget - 1.0 / 3.0).abs() < 1e-9);
+
+        let jaccard = peers::peers(&profiles, 3, GenreSimilarity::Jaccard, 1, 1).unwrap();
+        assert_eq!(jaccard.len(), 1);
+        assert_eq!(jaccard[0].genre, 0.0);
+        assert!(
+            peers::peers(&profiles, 1, GenreSimilarity::Cosine, 2, 5)
+                .unwrap()
+                .len()
+                == 1
+        );
+        assert!(peers::peers(&profiles, 99, GenreSimilarity::Cosine, 1, 5).is_none());
+    }
+
+    #[test]
+    fn genre_combination_performance() {
+        let genre_movie = |id: &str, year: i32, genres: &[i64], budget: i64, revenue: i64| Movie {
+            genres: genres.iter().copied().collect(),
+            release_date: NaiveDate::from_ymd_opt(year, 5, 1).unwrap(),
+            ..company_movie(id, &[1], budget, revenue)
+        };
+        let movies = vec![
+            genre_movie("1", 2001, &[28, 35], 100, 400),
+            genre_movie("2", 2005, &[28, 35, 18], 100, 200),
+            genre_movie("3", 2009, &[28], 100, 100),
+            genre_movie("4", 1995, &[28, 35], 0, 50),
+        ];
+        let decades = genre_combinations::genre_combinations(&movies);
+        assert_eq!(
+            decades.keys().copied().collect::<Vec<_>>(),
+            vec![1990, 2000]
+        );
+
+        let noughties = &decades[&2000];
+        let pair = &noughties[&vec![28, 35]];
+        assert_eq!(pair.movies, 2);
+        assert_eq!(pair.avg_budget(), Some(100.0));
+        assert_eq!(pair.avg_revenue(), 300.0);
+        assert_eq!(pair.roi(), Some(2.0));
+        // action on its own includes every action movie.
+        assert_eq!(noughties[&vec![28]].movies, 3);
+        assert!((noughties[&vec![28]].roi().unwrap() - 4.0 / 3.0).abs() < 1e-9);
+        assert_eq!(noughties[&vec![18, 28, 35]].movies, 1);
+        // an unknown budget still counts as a movie, just not towards roi.
+        assert_eq!(decades[&1990][&vec![28, 35]].roi(), None);
+
+        // only movie 3 is action and nothing else.
+        let alone = genre_combinations::genres_alone(&movies);
+        assert_eq!(alone[&2000][&28].movies, 1);
+        assert_eq!(alone[&2000][&28].roi(), Some(0.0));
+        assert!(!alone.contains_key(&1990));
+
+        assert_eq!(
+            genre_combinations::co_occurrence(&decades, 2, 2),
+            vec![(vec![28, 35], 3), (vec![18, 28], 1)]
+        );
+        let top = genre_combinations::top_in_decade(noughties, 2, 2, 5);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0, &vec![28, 35]);
+    }
+
+    #[test]
+    fn company_lifecycle() {
+        let dated = |id: &str, companies: &[i64], year: i32, revenue: i64| Movie {
+            release_date: NaiveDate::from_ymd_opt(year, 5, 1).unwrap(),
+            ..company_movie(id, companies, 10, revenue)
+        };
+        let movies = vec![
+            dated("1", &[1], 2000, 100),
+            dated("2", &[1], 2002, 500),
+            dated("3", &[1, 2], 2002, 100),
+            dated("4", &[1], 2006, 600),
+            dated("5", &[2], 2003, 50),
+        ];
+        let map = bucket_movies(&movies, full_credit);
+
+        let company = lifecycle::lifecycle(&map, 1).unwrap();
+        assert_eq!((company.first_year, company.last_year), (2000, 2006));
+        assert_eq!(company.span(), 7);
+        assert_eq!(company.active_years, 3);
+        assert_eq!(company.gaps, vec![(2001, 2001), (2003, 2005)]);
+        // 2002 and 2006 tie on revenue; the earlier one wins.
+        assert_eq!(company.peak_year, 2002);
+        assert_eq!(company.releases(2002), 2);
+        assert_eq!(company.releases(2004), 0);
+
+        let other = lifecycle::lifecycle(&map, 2).unwrap();
+        assert!(other.gaps.is_empty());
+        assert_eq!(other.peak_year, 2002);
+        assert!(lifecycle::lifecycle(&map, 99).is_none());
+    }
+
+    #[test]
+    fn forecasting() {
+        use forecast::{backtest, Errors, Model, MonthlySeries, Series};
+
+        let line: Vec<f64> = (0..10).map(|x| 5.0 + 2.0 * x as f64).collect();
+        assert_eq!(Model::Linear.forecast(&line, 2), Some(vec![25.0, 27.0]));
+        // never below zero.
+        assert_eq!(
+            Model::Linear.forecast(&[10.0, 5.0], 2),
+            Some(vec![0.0, 0.0])
+        );
+        assert_eq!(Model::Linear.forecast(&[1.0], 1), None);
+
+        // a flat year-on-year pattern comes back exactly.
+        let pattern = [1.0, 2.0, 3.0, 9.0, 5.0, 4.0, 8.0, 1.0, 2.0, 6.0, 3.0, 4.0];
+        let seasonal: Vec<f64> = pattern.iter().cycle().take(36).copied().collect();
+        let forecast = Model::HoltWinters.forecast(&seasonal, 13).unwrap();
+        assert!(forecast[..12]
+            .iter()
+            .zip(pattern)
+            .all(|(a, b)| (a - b).abs() < 1e-9));
+        assert!((forecast[12] - 1.0).abs() < 1e-9);
+        assert_eq!(Model::HoltWinters.forecast(&seasonal[..23], 1), None);
+
+        let errors = backtest(&seasonal, Model::HoltWinters, 1).unwrap();
+        assert!(errors.mae < 1e-9 && errors.rmse < 1e-9);
+        assert_eq!(backtest(&seasonal, Model::HoltWinters, 2), None);
+        assert_eq!(backtest(&seasonal, Model::Linear, usize::MAX), None);
+        assert_eq!(
+            Errors::of(&[0.0, 10.0], &[2.0, 5.0]),
+            Some(Errors {
+                mae: 3.5,
+                rmse: (14.5f64).sqrt(),
+                mape: Some(0.5),
+            })
+        );
+
+        let dated = |id: &str, year: i32, month: u32, revenue: i64| Movie {
+            release_date: NaiveDate::from_ymd_opt(year, month, 1).unwrap(),
+            ..company_movie(id, &[1], 10, revenue)
+        };
+        let movies = vec![
+            dated("1", 2000, 11, 100),
+            dated("2", 2000, 11, 50),
+            dated("3", 2002, 2, 70),
+        ];
+        let map = bucket_movies(&movies, full_credit);
+        let revenue = MonthlySeries::of(&map, 1, Series::Revenue).unwrap();
+        assert_eq!(revenue.start, (2000, 11));
+        // nov, dec, all of 2001 and jan are empty but for the first.
+        assert_eq!(revenue.values.len(), 16);
+        assert_eq!(revenue.values[0], 150.0);
+        assert_eq!(revenue.values[15], 70.0);
+        assert_eq!(revenue.month_at(16), (2002, 3));
+        assert_eq!(revenue.month_at(2), (2001, 1));
+        let releases = MonthlySeries::of(&map, 1, Series::Releases).unwrap();
+        assert_eq!(releases.values[0], 2.0);
+        assert!(MonthlySeries::of(&map, 99, Series::Revenue).is_none());
+    }
 }
@@ -0,0 +1,112 @@
+use chrono::Datelike;
+
+use crate::data::Movie;
+
+use super::{BucketYearMap, ById, Totals};
+
+// releases and revenue per calendar month, summed over every year.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MonthTotals {
+    pub releases: [u64; 12],
+    pub revenue: [i64; 12],
+}
+
+impl MonthTotals {
+    // the whole market, counting each movie once however many entities it belongs to.
+    pub fn of_movies(movies: &[Movie]) -> MonthTotals {
+        let mut totals = MonthTotals::default();
+        for movie in movies {
+            let month = movie.release_date.month0() as usize;
+            totals.releases[month] += 1;
+            totals.revenue[month] += movie.revenue;
+        }
+        totals
+    }
+
+    // one entity; the month array of BucketYearMap already has it split the right way.
+    pub fn of_entity<T: ById + Totals>(map: &BucketYearMap<T>, id: i64) -> MonthTotals {
+        let mut totals = MonthTotals::default();
+        for months in map.values() {
+            for (month, by_id) in months.iter().enumerate() {
+                if let Some(details) = by_id.get(&id) {
+                    totals.releases[month] += details.movie_count() as u64;
+                    totals.revenue[month] += details.revenue();
+                }
+            }
+        }
+        totals
+    }
+
+    pub fn release_share(&self) -> [f64; 12] {
+        shares(self.releases.map(|x| x as f64))
+    }
+
+    pub fn revenue_share(&self) -> [f64; 12] {
+        shares(self.revenue.map(|x| x as f64))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seasonality {
+    pub id: i64,
+    pub totals: MonthTotals,
+    pub release_share: [f64; 12],
+    pub revenue_share: [f64; 12],
+    // share divided by the market's share for the same month: 1.5 means the entity puts 50% more
+    // of its releases (or revenue) in that month than the market does. None where the market has
+    // nothing.
+    pub release_index: [Option<f64>; 12],
+    pub revenue_index: [Option<f64>; 12],
+    // 1-based months with the highest and lowest revenue per release, among months with any.
+    pub best_month: Option<u32>,
+    pub worst_month: Option<u32>,
+}
+
+// None when the entity has no releases in `map`.
+pub fn seasonality<T: ById + Totals>(
+    map: &BucketYearMap<T>,
+    id: i64,
+    market: &MonthTotals,
+) -> Option<Seasonality> {
+    let totals = MonthTotals::of_entity(map, id);
+    if totals.releases.iter().all(|x| *x == 0) {
+        return None;
+    }
+
+    let release_share = totals.release_share();
+    let revenue_share = totals.revenue_share();
+    let market_releases = market.release_share();
+    let market_revenue = market.revenue_share();
+
+    let per_release = (0..12).filter(|m| totals.releases[*m] > 0).map(|m| {
+        (
+            m as u32 + 1,
+            totals.revenue[m] as f64 / totals.releases[m] as f64,
+        )
+    });
+    let best_month = per_release
+        .clone()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|x| x.0);
+    let worst_month = per_release.min_by(|a, b| a.1.total_cmp(&b.1)).map(|x| x.0);
+
+    Some(Seasonality {
+        id,
+        release_index: index(&release_share, &market_releases),
+        revenue_index: index(&revenue_share, &market_revenue),
+        totals,
+        release_share,
+        revenue_share,
+        best_month,
+        worst_month,
+    })
+}
+
+fn shares(values: [f64; 12]) -> [f64; 12] {
+    let total: f64 = values.iter().sum();
+    values.map(|x| if total > 0.0 { x / total } else { 0.0 })
+}
+
+fn index(shares: &[f64; 12], market: &[f64; 12]) -> [Option<f64>; 12] {
+    std::array::from_fn(|m| (market[m] > 0.0).then(|| shares[m] / market[m]))
+}
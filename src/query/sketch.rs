@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+
+use crate::data::Movie;
+
+// quantiles come back within 1% of the true value, whatever the spread.
+const RELATIVE_ACCURACY: f64 = 0.01;
+
+// log-bucketed quantile sketch (DDSketch): a value lands in bucket ceil(log_gamma(|value|)), so
+// two sketches merge exactly by adding counts, the same way ById::sum adds totals.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Sketch {
+    positive: BTreeMap<i32, u64>,
+    // keyed on the magnitude.
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+fn gamma() -> f64 {
+    (1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY)
+}
+
+fn bucket(magnitude: f64) -> i32 {
+    (magnitude.ln() / gamma().ln()).ceil() as i32
+}
+
+// midpoint of the bucket in relative terms, so the error is the same either side.
+fn bucket_value(index: i32) -> f64 {
+    2.0 * gamma().powi(index) / (gamma() + 1.0)
+}
+
+impl Sketch {
+    pub fn of(value: f64) -> Sketch {
+        let mut sketch = Sketch::default();
+        sketch.add(value);
+        sketch
+    }
+
+    pub fn add(&mut self, value: f64) {
+        if value > 0.0 {
+            *self.positive.entry(bucket(value)).or_default() += 1;
+        } else if value < 0.0 {
+            *self.negative.entry(bucket(-value)).or_default() += 1;
+        } else {
+            self.zeros += 1;
+        }
+
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = if self.count == 0 {
+            value
+        } else {
+            self.max.max(value)
+        };
+        self.count += 1;
+    }
+
+    pub fn merge(&self, other: &Sketch) -> Sketch {
+        if self.count == 0 || other.count == 0 {
+            return if self.count == 0 { other } else { self }.clone();
+        }
+
+        let mut merged = self.clone();
+        for (index, count) in &other.positive {
+            *merged.positive.entry(*index).or_default() += count;
+        }
+        for (index, count) in &other.negative {
+            *merged.negative.entry(*index).or_default() += count;
+        }
+        merged.zeros += other.zeros;
+        merged.count += other.count;
+        merged.min = self.min.min(other.min);
+        merged.max = self.max.max(other.max);
+        merged
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    // q in [0, 1]; None when nothing was added. the ends are exact.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        } else if q <= 0.0 {
+            return Some(self.min);
+        } else if q >= 1.0 {
+            return Some(self.max);
+        }
+
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).floor() as u64;
+        Some(self.at_rank(rank))
+    }
+
+    // the mean of the two middle values when the count is even, like an exact median would.
+    pub fn median(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let (low, high) = ((self.count - 1) / 2, self.count / 2);
+        Some((self.at_rank(low) + self.at_rank(high)) / 2.0)
+    }
+
+    // the value `rank` places from the bottom, 0-based; the ends are exact.
+    fn at_rank(&self, rank: u64) -> f64 {
+        if rank == 0 {
+            return self.min;
+        } else if rank + 1 >= self.count {
+            return self.max;
+        }
+
+        let mut seen = 0;
+        let value = self
+            .buckets()
+            .find(|(_, count)| {
+                seen += count;
+                seen > rank
+            })
+            .map(|(value, _)| value)
+            .unwrap_or(self.max);
+
+        value.clamp(self.min, self.max)
+    }
+
+    // (lower, upper, count) per power of ten, lowest first; zeros get a (0, 0) bin. buckets are
+    // placed by their upper edge so exact powers of ten, common placeholders, land in their own
+    // decade.
+    pub fn histogram(&self) -> Vec<(f64, f64, u64)> {
+        let decade = |index: &i32| gamma().powi(*index).log10().floor() as i32;
+
+        let mut negative: BTreeMap<i32, u64> = BTreeMap::new();
+        for (index, count) in &self.negative {
+            *negative.entry(decade(index)).or_default() += count;
+        }
+        let mut positive: BTreeMap<i32, u64> = BTreeMap::new();
+        for (index, count) in &self.positive {
+            *positive.entry(decade(index)).or_default() += count;
+        }
+
+        let negative = negative
+            .into_iter()
+            .rev()
+            .map(|(decade, count)| (-(10f64.powi(decade + 1)), -(10f64.powi(decade)), count));
+        let zeros = (self.zeros > 0).then_some((0.0, 0.0, self.zeros));
+        let positive = positive
+            .into_iter()
+            .map(|(decade, count)| (10f64.powi(decade), 10f64.powi(decade + 1), count));
+        negative.chain(zeros).chain(positive).collect()
+    }
+
+    // (representative value, count), in ascending order of value.
+    fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        let negative = self
+            .negative
+            .iter()
+            .rev()
+            .map(|(index, count)| (-bucket_value(*index), *count));
+        let zeros = (self.zeros > 0).then_some((0.0, self.zeros));
+        let positive = self
+            .positive
+            .iter()
+            .map(|(index, count)| (bucket_value(*index), *count));
+        negative.chain(zeros).chain(positive)
+    }
+}
+
+// per-movie spreads kept alongside the sums of an aggregate.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Distributions {
+    pub revenue: Sketch,
+    pub profit: Sketch,
+    pub popularity: Sketch,
+}
+
+impl Distributions {
+    pub fn of(movie: &Movie) -> Distributions {
+        Distributions {
+            revenue: Sketch::of(movie.revenue as f64),
+            profit: Sketch::of(movie.profit as f64),
+            popularity: Sketch::of(movie.avg_populatarity as f64),
+        }
+    }
+
+    pub fn merge(&self, other: &Distributions) -> Distributions {
+        Distributions {
+            revenue: self.revenue.merge(&other.revenue),
+            profit: self.profit.merge(&other.profit),
+            popularity: self.popularity.merge(&other.popularity),
+        }
+    }
+
+    // missing on one side counts as nothing to add, like Adjusted::combine.
+    pub fn combine(a: &Option<Distributions>, b: &Option<Distributions>) -> Option<Distributions> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.merge(b)),
+            (Some(x), None) | (None, Some(x)) => Some(x.clone()),
+            (None, None) => None,
+        }
+    }
+
+    pub fn by_name(&self) -> [(&'static str, &Sketch); 3] {
+        [
+            ("revenue", &self.revenue),
+            ("profit", &self.profit),
+            ("popularity", &self.popularity),
+        ]
+    }
+}
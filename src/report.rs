@@ -0,0 +1,713 @@
+use chrono::Datelike;
+use itertools::Itertools;
+use json::{object, JsonValue};
+
+use crate::{
+    data::{country_code, Catalog, Dictionary, Movie, Reject},
+    query::{
+        allocation::Allocation,
+        bucket_movies,
+        by_collection::{franchise_summary, movie_to_collection_details},
+        by_country::{country_company_breakdown, country_split, IN_COUNTRY, OUTSIDE_COUNTRY},
+        by_keywords::{keyword_trend, rising_keywords},
+        by_production_companies::{scale, ProdCompanyDetails},
+        coproduction::CoproductionGraph,
+        entity::EntityDetails,
+        forecast::{backtest, Model, MonthlySeries, Series},
+        genre_combinations::{
+            co_occurrence, genre_combinations, genres_alone, top_in_decade, MAX_COMBINATION,
+        },
+        lifecycle::lifecycle,
+        market_share::{market_share, CONCENTRATION_TOP},
+        peers::{peers, profiles, GenreSimilarity},
+        seasonality::{seasonality, MonthTotals},
+        sum_all, sum_by_year, top, BucketYearMap, ById, Metric, Totals,
+    },
+    serve::ranked,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // the full report.
+    Text,
+    // only the yearly ranking, as one json document.
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        }
+    }
+}
+
+// what the yearly ranking groups movies by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Country,
+    Company,
+    Genre,
+}
+
+impl GroupBy {
+    pub fn from_name(name: &str) -> Option<GroupBy> {
+        match name {
+            "country" => Some(Self::Country),
+            "company" => Some(Self::Company),
+            "genre" => Some(Self::Genre),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Country => "country",
+            Self::Company => "company",
+            Self::Genre => "genre",
+        }
+    }
+
+    // the ranking's heading in the text report.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Country => "countries",
+            Self::Company => "companies",
+            Self::Genre => "genres",
+        }
+    }
+}
+
+// print_top_by_year, or the same ranking as json.
+pub fn print_ranking<T: ById + Clone + Totals>(
+    format: Format,
+    group_by: GroupBy,
+    map: &BucketYearMap<T>,
+    names: &Dictionary,
+    metric: Metric,
+    limit: usize,
+) {
+    match format {
+        Format::Text => print_top_by_year(group_by.title(), map, names, metric, limit),
+        Format::Json => println!(
+            "{}",
+            ranking_json(group_by, map, names, metric, limit).dump()
+        ),
+    }
+}
+
+// {"by": .., "metric": .., "years": [{"year": .., "results": [..]}]}, results as in /top.
+pub fn ranking_json<T: ById + Clone + Totals>(
+    group_by: GroupBy,
+    map: &BucketYearMap<T>,
+    names: &Dictionary,
+    metric: Metric,
+    limit: usize,
+) -> JsonValue {
+    let years: Vec<JsonValue> = map
+        .keys()
+        .map(|year| (year, top(map, *year, metric, limit)))
+        .filter(|(_, entries)| !entries.is_empty())
+        .map(|(year, entries)| {
+            object! {
+                year: *year,
+                results: ranked(&entries, names, metric),
+            }
+        })
+        .collect();
+
+    object! {
+        by: group_by.name(),
+        metric: metric.name(),
+        years: years,
+    }
+}
+
+// one block per year listing the best `limit` entities by `metric`.
+pub fn print_top_by_year<T: ById + Clone + Totals>(
+    title: &str,
+    map: &BucketYearMap<T>,
+    names: &Dictionary,
+    metric: Metric,
+    limit: usize,
+) {
+    println!("{title} by {}:", metric.name());
+    for year in map.keys() {
+        let ranked = top(map, *year, metric, limit);
+        if ranked.is_empty() {
+            continue;
+        }
+
+        println!("  {year}:");
+        for entry in ranked {
+            println!(
+                "    {:<40} movies: {:>3} revenue: {:>14} profit: {:>14}{}{}",
+                name_of(names, entry.id()),
+                entry.movie_count(),
+                entry.revenue(),
+                entry.profit(),
+                returns_columns(entry.as_ref()),
+                adjusted_columns(entry.as_ref()),
+            );
+        }
+    }
+}
+
+// fastest growing keywords, each followed by its per-year movie count and revenue.
+pub fn print_rising_keywords(
+    map: &BucketYearMap<EntityDetails>,
+    names: &Dictionary,
+    min_movies: usize,
+    limit: usize,
+) {
+    println!("rising keywords:");
+    for (id, slope) in rising_keywords(map, min_movies, limit) {
+        println!("  {} ({slope:+.3} movies/year)", name_of(names, id));
+        for (year, trend) in keyword_trend(map, id) {
+            println!(
+                "    {year}: movies: {:>3} revenue: {:>14}",
+                trend.movies, trend.revenue
+            );
+        }
+    }
+}
+
+// box office of movies produced in `code` against everything else, per year.
+pub fn print_country_split(movies: &[Movie], code: &str) {
+    println!("{code} vs. non-{code} box office:");
+    for (year, by_id) in sum_by_year(&country_split(movies, code)) {
+        let revenue = |id| by_id.get(&id).map(|x| x.revenue()).unwrap_or(0);
+        let (inside, outside) = (revenue(IN_COUNTRY), revenue(OUTSIDE_COUNTRY));
+        let share = inside as f64 / (inside + outside).max(1) as f64 * 100.0;
+        println!("  {year}: {code}: {inside:>14} non-{code}: {outside:>14} ({share:.1}% {code})");
+    }
+}
+
+pub fn print_country_company_breakdown(movies: &[Movie], companies: &Dictionary, limit: usize) {
+    println!("top country x company by revenue:");
+    country_company_breakdown(movies)
+        .into_iter()
+        .sorted_by(|a, b| b.1.revenue.cmp(&a.1.revenue).then(a.0.cmp(&b.0)))
+        .take(limit)
+        .for_each(|((country, company), totals)| {
+            println!(
+                "  {:<4} {:<40} movies: {:>4} revenue: {:>14} profit: {:>14}",
+                country_code(country),
+                name_of(companies, company),
+                totals.movies,
+                totals.revenue,
+                totals.profit,
+            )
+        });
+}
+
+// biggest franchises by cumulative revenue, with release cadence and installment-to-installment
+// revenue change.
+pub fn print_franchises(movies: &[Movie], limit: usize) {
+    let by_collection = bucket_movies(movies, movie_to_collection_details);
+
+    println!("franchises by revenue:");
+    sum_all(&by_collection)
+        .values()
+        .flat_map(|details| franchise_summary(details))
+        .sorted_by(|a, b| b.revenue.cmp(&a.revenue).then(a.id.cmp(&b.id)))
+        .take(limit)
+        .for_each(|franchise| {
+            let cadence = franchise
+                .avg_gap_days
+                .map(|days| format!("{:.1} years apart", days / 365.25))
+                .unwrap_or_else(|| "single release".to_string());
+            let trend = franchise
+                .revenue_trend
+                .iter()
+                .map(|change| {
+                    change
+                        .map(|x| format!("{:+.0}%", x * 100.0))
+                        .unwrap_or_else(|| "n/a".to_string())
+                })
+                .join(", ");
+
+            println!(
+                "  {:<40} installments: {:>2} revenue: {:>14} profit: {:>14} {} to {}, {cadence}",
+                franchise.name,
+                franchise.installments,
+                franchise.revenue,
+                franchise.profit,
+                franchise.first_release,
+                franchise.last_release,
+            );
+            if !trend.is_empty() {
+                println!("    revenue trend: {trend}");
+            }
+        });
+}
+
+// percentiles and histogram of per-movie revenue for the biggest `limit` companies overall.
+pub fn print_distributions(
+    map: &BucketYearMap<ProdCompanyDetails>,
+    companies: &Dictionary,
+    allocation: &Allocation,
+    limit: usize,
+) {
+    println!("company revenue distributions ({}):", allocation.describe());
+    let summed = sum_all(map);
+    let biggest = summed
+        .values()
+        .sorted_by_key(|x| (-x.revenue, x.id))
+        .take(limit);
+
+    for company in biggest {
+        let Some(sketch) = company.distributions.as_ref().map(|x| &x.revenue) else {
+            continue;
+        };
+        println!(
+            "  {:<40} movies: {:>3} p50: {:>14.0} p90: {:>14.0} p99: {:>14.0}",
+            name_of(companies, company.id),
+            sketch.count(),
+            sketch.quantile(0.5).unwrap_or_default(),
+            sketch.quantile(0.9).unwrap_or_default(),
+            sketch.quantile(0.99).unwrap_or_default(),
+        );
+        for (lower, upper, count) in sketch.histogram() {
+            println!("    {lower:>16.0} .. {upper:<16.0} {count:>5}");
+        }
+    }
+}
+
+// release and revenue mix by month for the market and the biggest `limit` entities; the index
+// row is each month's revenue share against the market's. `allocation` is only for companies.
+pub fn print_seasonality<T: ById + Clone + Totals>(
+    title: &str,
+    map: &BucketYearMap<T>,
+    market: &MonthTotals,
+    names: &Dictionary,
+    allocation: Option<&Allocation>,
+    limit: usize,
+) {
+    let percent = |shares: [f64; 12]| {
+        shares
+            .iter()
+            .map(|x| format!("{:>5.1}", x * 100.0))
+            .join(" ")
+    };
+
+    println!(
+        "{title} seasonality (jan .. dec{}):",
+        allocation
+            .map(|x| format!(", {}", x.describe()))
+            .unwrap_or_default()
+    );
+    println!(
+        "  {:<40} releases% {}",
+        "market",
+        percent(market.release_share())
+    );
+    println!("  {:<40} revenue%  {}", "", percent(market.revenue_share()));
+
+    let summed = sum_all(map);
+    let biggest = summed
+        .values()
+        .sorted_by_key(|x| (-x.revenue(), x.id()))
+        .take(limit);
+
+    for entity in biggest {
+        let Some(seasonality) = seasonality(map, entity.id(), market) else {
+            continue;
+        };
+        let index = seasonality
+            .revenue_index
+            .iter()
+            .map(|x| {
+                x.map(|x| format!("{x:>5.2}"))
+                    .unwrap_or(format!("{:>5}", "-"))
+            })
+            .join(" ");
+        println!(
+            "  {:<40} revenue%  {} best: {} worst: {}",
+            name_of(names, entity.id()),
+            percent(seasonality.revenue_share),
+            seasonality.best_month.unwrap_or_default(),
+            seasonality.worst_month.unwrap_or_default(),
+        );
+        println!("  {:<40} index     {index}", "");
+    }
+}
+
+// concentration per year and the biggest `limit` companies' share of it; shift is the change in
+// revenue share in points since the year before.
+pub fn print_market_share(
+    map: &BucketYearMap<ProdCompanyDetails>,
+    companies: &Dictionary,
+    allocation: &Allocation,
+    limit: usize,
+) {
+    println!("market share ({}):", allocation.describe());
+    for period in market_share(map) {
+        println!(
+            "market share {}: revenue: {:>14} releases: {:>5} hhi: {:>7.1} top{CONCENTRATION_TOP}: {:>5.1}%",
+            period.year,
+            period.revenue,
+            period.releases,
+            period.hhi,
+            period.top_share * 100.0
+        );
+        for share in period.shares.iter().take(limit) {
+            let shift = share
+                .shift
+                .map(|x| format!(" shift: {:>+6.1}", x * 100.0))
+                .unwrap_or_default();
+            println!(
+                "  {:<40} revenue: {:>5.1}% releases: {:>5.1}%{shift}",
+                name_of(companies, share.id),
+                share.revenue_share * 100.0,
+                share.release_share * 100.0,
+            );
+        }
+    }
+}
+
+// the most frequent partners, and how the companies cluster.
+pub fn print_coproduction(graph: &CoproductionGraph, companies: &Dictionary, limit: usize) {
+    println!("co-productions:");
+    for ((a, b), edge) in graph.top_pairs(limit) {
+        println!(
+            "  {:<40} {:<40} movies: {:>3} revenue: {:>14}",
+            name_of(companies, a),
+            name_of(companies, b),
+            edge.movies,
+            edge.revenue
+        );
+    }
+
+    let components = graph.components();
+    let alone = components.iter().filter(|x| x.len() == 1).count();
+    println!(
+        "  {} companies in {} components, {alone} never co-produced",
+        graph.nodes.len(),
+        components.len()
+    );
+    for component in components.iter().filter(|x| x.len() > 1).take(limit) {
+        let more = if component.len() > 3 { ", ..." } else { "" };
+        let names = component
+            .iter()
+            .take(3)
+            .map(|x| name_of(companies, *x))
+            .join(", ");
+        println!("  {:>5} companies: {names}{more}", component.len());
+    }
+}
+
+// for each of the biggest `limit` companies, the `limit` most similar ones with `min_movies`.
+pub fn print_peers(
+    map: &BucketYearMap<ProdCompanyDetails>,
+    companies: &Dictionary,
+    allocation: &Allocation,
+    similarity: GenreSimilarity,
+    min_movies: usize,
+    limit: usize,
+) {
+    println!(
+        "peers (genres by {}, {}):",
+        similarity.name(),
+        allocation.describe()
+    );
+    let profiles = profiles(map);
+    let biggest = sum_all(map)
+        .into_values()
+        .sorted_by_key(|x| (-x.revenue, x.id))
+        .take(limit);
+
+    for company in biggest {
+        println!("  {}:", name_of(companies, company.id));
+        for peer in peers(&profiles, company.id, similarity, min_movies, limit).unwrap_or_default()
+        {
+            println!(
+                "    {:<40} score: {:.2} genres: {:.2} budget: {:.2} cadence: {:.2}",
+                name_of(companies, peer.id),
+                peer.score,
+                peer.genre,
+                peer.budget,
+                peer.cadence
+            );
+        }
+    }
+}
+
+// which genres go together most, then per decade how pairs and triples did next to each of
+// their genres on its own.
+pub fn print_genre_combinations(
+    movies: &[Movie],
+    genres: &Dictionary,
+    min_movies: usize,
+    limit: usize,
+) {
+    let decades = genre_combinations(movies);
+    let alone = genres_alone(movies);
+    let label = |combination: &[i64]| combination.iter().map(|x| name_of(genres, *x)).join("+");
+    let roi = |x: Option<f64>| x.map(|x| format!("{x:.2}")).unwrap_or("-".to_string());
+
+    for size in 2..=MAX_COMBINATION {
+        println!("most common {size}-genre combinations:");
+        for (combination, movies) in co_occurrence(&decades, size, limit) {
+            println!("  {:<50} movies: {movies:>5}", label(&combination));
+        }
+    }
+
+    for (decade, combinations) in &decades {
+        println!("genre combinations {decade}s (alone: roi of single-genre movies):");
+        let singles = alone.get(decade);
+        for size in 2..=MAX_COMBINATION {
+            for (combination, stats) in top_in_decade(combinations, size, min_movies, limit) {
+                let alone = combination
+                    .iter()
+                    .map(|x| {
+                        let single = singles.and_then(|s| s.get(x)).and_then(|x| x.roi());
+                        format!("{} {}", name_of(genres, *x), roi(single))
+                    })
+                    .join(", ");
+                println!(
+                    "  {:<50} movies: {:>4} avg budget: {:>12.0} avg revenue: {:>12.0} roi: {:>5} (alone: {alone})",
+                    label(combination),
+                    stats.movies,
+                    stats.avg_budget().unwrap_or_default(),
+                    stats.avg_revenue(),
+                    roi(stats.roi()),
+                );
+            }
+        }
+    }
+}
+
+// a title in `report company`, with the part of it the allocation gives the company.
+#[derive(Debug, Clone)]
+pub struct Credit<'a> {
+    pub movie: &'a Movie,
+    pub share: f64,
+    pub revenue: i64,
+}
+
+// the movies the allocation put in `id`'s timeline, oldest first, so under lead a co-production
+// only shows up for its lead.
+pub fn company_credits<'a>(
+    catalog: &'a Catalog,
+    id: i64,
+    allocation: &Allocation,
+) -> Vec<Credit<'a>> {
+    catalog
+        .movies
+        .iter()
+        .flat_map(|movie| {
+            let share = allocation.share_of(movie, id)?;
+            Some(Credit {
+                movie,
+                share,
+                revenue: scale(movie.revenue, share),
+            })
+        })
+        .sorted_by_key(|x| (x.movie.release_date, x.movie.id.clone()))
+        .collect()
+}
+
+// `report company <id>`: lifecycle summary, then every year with the titles released in it.
+// false when the company has no releases.
+pub fn print_company_report(
+    map: &BucketYearMap<ProdCompanyDetails>,
+    catalog: &Catalog,
+    id: i64,
+    allocation: &Allocation,
+) -> bool {
+    let Some(lifecycle) = lifecycle(map, id) else {
+        return false;
+    };
+    let credits = company_credits(catalog, id, allocation);
+    let describe = |x: Option<&Credit>| {
+        x.map(|x| format!("{} {}", x.movie.release_date, x.movie.title))
+            .unwrap_or_default()
+    };
+    let gaps = lifecycle
+        .gaps
+        .iter()
+        .map(|(from, to)| {
+            if from == to {
+                from.to_string()
+            } else {
+                format!("{from}-{to}")
+            }
+        })
+        .join(", ");
+
+    println!(
+        "{} ({id}), {}:",
+        name_of(&catalog.companies, id),
+        allocation.describe()
+    );
+    println!("  first release: {}", describe(credits.first()));
+    println!("  last release:  {}", describe(credits.last()));
+    println!(
+        "  active {} of {} years, gaps: {}",
+        lifecycle.active_years,
+        lifecycle.span(),
+        if gaps.is_empty() { "none" } else { &gaps }
+    );
+    println!(
+        "  peak year: {} revenue: {}",
+        lifecycle.peak_year, lifecycle.yearly[&lifecycle.peak_year].revenue
+    );
+
+    let by_year = credits
+        .iter()
+        .into_group_map_by(|x| x.movie.release_date.year());
+    for (year, totals) in &lifecycle.yearly {
+        println!(
+            "  {year}: releases: {:>3} revenue: {:>14} profit: {:>14}{}",
+            lifecycle.releases(*year),
+            totals.revenue,
+            totals.profit,
+            returns_columns(totals.as_ref())
+        );
+        for credit in by_year.get(year).into_iter().flatten() {
+            println!(
+                "    {} {:<50} revenue: {:>14} share: {:>5.1}%",
+                credit.movie.release_date,
+                credit.movie.title,
+                credit.revenue,
+                credit.share * 100.0
+            );
+        }
+    }
+    true
+}
+
+// the next `periods` months of revenue and releases for the biggest `limit` entities, from each
+// model, with how far off it was over the last `holdout_years` when they were held back.
+// `allocation` is only for companies.
+pub fn print_forecasts<T: ById + Clone + Totals>(
+    title: &str,
+    map: &BucketYearMap<T>,
+    names: &Dictionary,
+    allocation: Option<&Allocation>,
+    periods: usize,
+    holdout_years: usize,
+    limit: usize,
+) {
+    println!(
+        "{title} forecasts (next {periods} months, backtested on the last {holdout_years} years{}):",
+        allocation
+            .map(|x| format!(", {}", x.describe()))
+            .unwrap_or_default()
+    );
+    let biggest = sum_all(map)
+        .into_values()
+        .sorted_by_key(|x| (-x.revenue(), x.id()))
+        .take(limit);
+
+    for entity in biggest {
+        println!("  {}:", name_of(names, entity.id()));
+        for series in [Series::Revenue, Series::Releases] {
+            let Some(monthly) = MonthlySeries::of(map, entity.id(), series) else {
+                continue;
+            };
+            // release counts are small enough that whole numbers hide everything.
+            let digits = if series == Series::Releases { 2 } else { 0 };
+            for model in [Model::Linear, Model::HoltWinters] {
+                let Some(forecast) = model.forecast(&monthly.values, periods) else {
+                    println!(
+                        "    {:<8} {:<12} not enough history",
+                        series.name(),
+                        model.name()
+                    );
+                    continue;
+                };
+                let errors = backtest(&monthly.values, model, holdout_years)
+                    .map(|x| {
+                        let mape = x
+                            .mape
+                            .map(|x| format!("{:.0}%", x * 100.0))
+                            .unwrap_or("-".to_string());
+                        format!(
+                            " mae: {:.digits$} rmse: {:.digits$} mape: {mape}",
+                            x.mae, x.rmse
+                        )
+                    })
+                    .unwrap_or_default();
+                let (year, month) = monthly.month_at(monthly.values.len());
+                println!(
+                    "    {:<8} {:<12} from {year}-{month:02} total: {:>14.digits$}{errors}",
+                    series.name(),
+                    model.name(),
+                    forecast.iter().sum::<f64>(),
+                );
+                println!(
+                    "      {}",
+                    forecast.iter().map(|x| format!("{x:.digits$}")).join(" ")
+                );
+            }
+        }
+    }
+}
+
+// counts per kind, so a run shows what was left out without opening the rejects file.
+pub fn print_rejects_summary(rejects: &[Reject]) {
+    let counts = rejects.iter().counts_by(|x| x.reason.describe().0);
+    for (kind, count) in counts.iter().sorted() {
+        println!("rejects {kind}: {count}");
+    }
+}
+
+// one row per reject: movie_id,source,line,reason,detail.
+pub fn write_rejects(path: &str, rejects: &[Reject]) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["movie_id", "source", "line", "reason", "detail"])?;
+    for reject in rejects {
+        let (kind, detail) = reject.reason.describe();
+        let line = reject.line.map(|x| x.to_string()).unwrap_or_default();
+        let source = reject.source.as_deref().unwrap_or_default();
+        writer.write_record([reject.movie_id.as_str(), source, &line, &kind, &detail])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// blank when none of the movies has a known budget.
+fn returns_columns<T: Totals>(totals: &T) -> String {
+    let returns = totals.returns();
+    match (returns.roi(), returns.hit_rate(), returns.median_profit()) {
+        (Some(roi), Some(hit_rate), Some(median)) => format!(
+            " roi: {roi:>7.2} median profit: {median:>14.0} hits: {:>4.0}% flops: {:>3}",
+            hit_rate * 100.0,
+            returns.flops
+        ),
+        _ => String::new(),
+    }
+}
+
+// constant dollar figures next to the nominal ones, when the inflation stage ran.
+fn adjusted_columns<T: Totals>(totals: &T) -> String {
+    totals
+        .adjusted()
+        .map(|x| {
+            format!(
+                " adjusted revenue: {:>14} adjusted profit: {:>14}",
+                x.revenue, x.profit
+            )
+        })
+        .unwrap_or_default()
+}
+
+pub fn name_of(names: &Dictionary, id: i64) -> String {
+    names
+        .get(&id)
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| format!("#{id}"))
+}
+
+mod tests;
@@ -0,0 +1,100 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+    use chrono::NaiveDate;
+    use kstring::KString;
+
+    fn movie(id: &str, title: &str, date: (i32, u32, u32), companies: &[i64]) -> Movie {
+        Movie {
+            id: KString::from_ref(id),
+            title: KString::from_ref(title),
+            release_date: NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            production_companies: companies.iter().copied().collect(),
+            budget: 100,
+            revenue: 300,
+            profit: 200,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn company_credits_under_allocation() {
+        let catalog = Catalog {
+            movies: vec![
+                movie("3", "Sequel", (2004, 7, 1), &[1]),
+                movie("2", "Co-production", (2002, 3, 15), &[1, 2]),
+                movie("1", "Debut", (2000, 1, 10), &[1]),
+                movie("4", "Elsewhere", (2006, 1, 1), &[2]),
+            ],
+            ..Default::default()
+        };
+        let summary = |credits: &[Credit]| -> Vec<(String, f64, i64)> {
+            credits
+                .iter()
+                .map(|x| (x.movie.title.to_string(), x.share, x.revenue))
+                .collect()
+        };
+
+        let credits = company_credits(&catalog, 1, &Allocation::Equal);
+        assert_eq!(
+            credits.first().map(|x| x.movie.release_date),
+            NaiveDate::from_ymd_opt(2000, 1, 10)
+        );
+        assert_eq!(
+            credits.last().map(|x| x.movie.release_date),
+            NaiveDate::from_ymd_opt(2004, 7, 1)
+        );
+        assert_eq!(
+            summary(&credits),
+            vec![
+                ("Debut".to_string(), 1.0, 300),
+                ("Co-production".to_string(), 0.5, 150),
+                ("Sequel".to_string(), 1.0, 300),
+            ]
+        );
+
+        // the co-production is company 2's first release, at half the revenue.
+        let other = company_credits(&catalog, 2, &Allocation::Equal);
+        assert_eq!(
+            summary(&other),
+            vec![
+                ("Co-production".to_string(), 0.5, 150),
+                ("Elsewhere".to_string(), 1.0, 300),
+            ]
+        );
+
+        // without a known lead the lowest id takes the co-production, so 2 starts later.
+        let lead = company_credits(&catalog, 2, &Allocation::Lead);
+        assert_eq!(summary(&lead), vec![("Elsewhere".to_string(), 1.0, 300)]);
+        assert!(company_credits(&catalog, 99, &Allocation::Equal).is_empty());
+    }
+
+    #[test]
+    fn ranking_as_json() {
+        let movies = vec![
+            movie("1", "Debut", (2000, 1, 10), &[1]),
+            movie("2", "Co-production", (2000, 3, 15), &[1, 2]),
+            movie("3", "Sequel", (2004, 7, 1), &[2]),
+        ];
+        let map = bucket_movies(&movies, |x| {
+            crate::query::by_production_companies::movie_to_details(x, &Allocation::Equal, false)
+        });
+        let names = Dictionary::from([(1, KString::from_static("Pixar"))]);
+
+        let json = ranking_json(GroupBy::Company, &map, &names, Metric::Revenue, 1);
+        assert_eq!(json["by"], "company");
+        assert_eq!(json["metric"], "revenue");
+        assert_eq!(json["years"].len(), 2);
+        let first = &json["years"][0];
+        assert_eq!(first["year"], 2000);
+        assert_eq!(first["results"].len(), 1);
+        assert_eq!(first["results"][0]["id"], 1);
+        assert_eq!(first["results"][0]["name"], "Pixar");
+        assert_eq!(first["results"][0]["revenue"], 450);
+        assert!(json["years"][1]["results"][0]["name"].is_null());
+
+        assert_eq!(Format::from_name("json"), Some(Format::Json));
+        assert_eq!(GroupBy::from_name("studio"), None);
+    }
+}
@@ -0,0 +1,590 @@
+use json::{object, JsonValue};
+use std::{collections::HashMap, rc::Rc};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    data::{Catalog, Dictionary},
+    query::{
+        allocation::Allocation,
+        bucket_movies,
+        by_genres::movie_to_genre_details,
+        by_production_companies::{movie_to_details, ProdCompanyDetails},
+        coproduction::CoproductionGraph,
+        entity::EntityDetails,
+        forecast::{backtest, Model, MonthlySeries, Series, MAX_HOLDOUT_YEARS, MAX_PERIODS},
+        genre_combinations::{
+            genre_combinations, top_in_decade, CombinationsByDecade, MAX_COMBINATION,
+        },
+        market_share::market_share,
+        peers::{self, peers, profiles, CompanyProfile, GenreSimilarity},
+        seasonality::{seasonality, MonthTotals},
+        sketch::Distributions,
+        timeline, top, BucketYearMap, ById, Metric, Totals,
+    },
+};
+
+const DEFAULT_TOP_LIMIT: usize = 10;
+
+// everything the endpoints answer from; built once at startup.
+pub struct Api {
+    catalog: Catalog,
+    by_company: BucketYearMap<ProdCompanyDetails>,
+    by_genre: BucketYearMap<EntityDetails>,
+    market: MonthTotals,
+    graph: CoproductionGraph,
+    profiles: HashMap<i64, CompanyProfile>,
+    combinations: CombinationsByDecade,
+    // used when a forecast request doesn't give its own.
+    forecast_periods: usize,
+    holdout_years: usize,
+}
+
+impl Api {
+    // `distributions` keeps per-company sketches, reported as percentiles and a histogram.
+    // `allocation` decides how company figures share a co-produced movie.
+    pub fn new(
+        catalog: Catalog,
+        distributions: bool,
+        allocation: &Allocation,
+        forecast_periods: usize,
+        holdout_years: usize,
+    ) -> Api {
+        let by_company = bucket_movies(&catalog.movies, |x| {
+            movie_to_details(x, allocation, distributions)
+        });
+        let by_genre = bucket_movies(&catalog.movies, movie_to_genre_details);
+        let market = MonthTotals::of_movies(&catalog.movies);
+        let graph = CoproductionGraph::of(&catalog.movies);
+        let profiles = profiles(&by_company);
+        let combinations = genre_combinations(&catalog.movies);
+        Api {
+            catalog,
+            by_company,
+            by_genre,
+            market,
+            graph,
+            profiles,
+            combinations,
+            forecast_periods,
+            holdout_years,
+        }
+    }
+
+    // `url` is the raw request target, e.g. "/top?metric=profit&year=2010".
+    pub fn route(&self, method: &Method, url: &str) -> (u16, JsonValue) {
+        if *method != Method::Get {
+            return error(405, "only GET is supported");
+        }
+
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let query = parse_query(query);
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        match segments.as_slice() {
+            ["companies", id, "timeline"] => match id.parse() {
+                Ok(id) => entity_timeline(&self.by_company, &self.catalog.companies, id),
+                Err(_) => error(400, "company id must be a number"),
+            },
+            ["genres", id, "timeline"] => match id.parse() {
+                Ok(id) => entity_timeline(&self.by_genre, &self.catalog.genres, id),
+                Err(_) => error(400, "genre id must be a number"),
+            },
+            ["companies", id, "seasonality"] => match id.parse() {
+                Ok(id) => entity_seasonality(&self.by_company, &self.market, id),
+                Err(_) => error(400, "company id must be a number"),
+            },
+            ["genres", id, "seasonality"] => match id.parse() {
+                Ok(id) => entity_seasonality(&self.by_genre, &self.market, id),
+                Err(_) => error(400, "genre id must be a number"),
+            },
+            ["companies", id, "partners"] => match id.parse() {
+                Ok(id) => self.partners(id),
+                Err(_) => error(400, "company id must be a number"),
+            },
+            ["companies", id, "peers"] => match id.parse() {
+                Ok(id) => self.peers(id, &query),
+                Err(_) => error(400, "company id must be a number"),
+            },
+            ["companies", id, "forecast"] => match id.parse() {
+                Ok(id) => self.entity_forecast(&self.by_company, id, &query),
+                Err(_) => error(400, "company id must be a number"),
+            },
+            ["genres", id, "forecast"] => match id.parse() {
+                Ok(id) => self.entity_forecast(&self.by_genre, id, &query),
+                Err(_) => error(400, "genre id must be a number"),
+            },
+            ["genres", "combinations"] => self.genre_combinations(&query),
+            ["top"] => self.top(&query),
+            ["market-share"] => self.market_share(&query),
+            _ => error(404, "not found"),
+        }
+    }
+
+    // /top?metric=profit&year=2010[&limit=10][&by=companies|genres]
+    fn top(&self, query: &HashMap<String, String>) -> (u16, JsonValue) {
+        let metric = match query.get("metric").map(|m| Metric::from_name(m)) {
+            Some(Some(metric)) => metric,
+            Some(None) => return error(400, "unknown metric"),
+            None => Metric::Revenue,
+        };
+        let year = match query.get("year").map(|y| y.parse::<i32>()) {
+            Some(Ok(year)) => year,
+            _ => return error(400, "year is required and must be a number"),
+        };
+        let limit = match query.get("limit").map(|l| l.parse::<usize>()) {
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return error(400, "limit must be a number"),
+            None => DEFAULT_TOP_LIMIT,
+        };
+
+        let results = match query.get("by").map(String::as_str) {
+            None | Some("companies") => ranked(
+                &top(&self.by_company, year, metric, limit),
+                &self.catalog.companies,
+                metric,
+            ),
+            Some("genres") => ranked(
+                &top(&self.by_genre, year, metric, limit),
+                &self.catalog.genres,
+                metric,
+            ),
+            Some(_) => return error(400, "by must be companies or genres"),
+        };
+
+        (
+            200,
+            object! {
+                metric: metric.name(),
+                year: year,
+                results: results,
+            },
+        )
+    }
+
+    // everyone the company co-produced with, overall and per year.
+    fn partners(&self, id: i64) -> (u16, JsonValue) {
+        if !self.graph.nodes.contains(&id) {
+            return error(404, "unknown id");
+        }
+        let names = &self.catalog.companies;
+
+        let partners: Vec<JsonValue> = self
+            .graph
+            .partners(id)
+            .into_iter()
+            .map(|(partner, edge)| {
+                object! {
+                    id: partner,
+                    name: names.get(&partner).map(|n| n.as_str()),
+                    movies: edge.movies,
+                    revenue: edge.revenue,
+                }
+            })
+            .collect();
+        let by_year: Vec<JsonValue> = self
+            .graph
+            .partners_by_year(id)
+            .into_iter()
+            .map(|(year, partners)| {
+                let partners: Vec<JsonValue> = partners
+                    .into_iter()
+                    .map(|(partner, movies)| object! { id: partner, movies: movies })
+                    .collect();
+                object! { year: year, partners: partners }
+            })
+            .collect();
+
+        (
+            200,
+            object! {
+                id: id,
+                name: names.get(&id).map(|n| n.as_str()),
+                partners: partners,
+                by_year: by_year,
+            },
+        )
+    }
+
+    // /companies/{id}/peers[?limit=10][&similarity=cosine|jaccard][&min_movies=3]
+    fn peers(&self, id: i64, query: &HashMap<String, String>) -> (u16, JsonValue) {
+        let similarity = match query
+            .get("similarity")
+            .map(|x| GenreSimilarity::from_name(x))
+        {
+            Some(Some(similarity)) => similarity,
+            Some(None) => return error(400, "similarity must be cosine or jaccard"),
+            None => GenreSimilarity::Cosine,
+        };
+        let limit = match query.get("limit").map(|l| l.parse::<usize>()) {
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return error(400, "limit must be a number"),
+            None => DEFAULT_TOP_LIMIT,
+        };
+        let min_movies = match query.get("min_movies").map(|l| l.parse::<usize>()) {
+            Some(Ok(min_movies)) => min_movies,
+            Some(Err(_)) => return error(400, "min_movies must be a number"),
+            None => peers::MIN_MOVIES,
+        };
+
+        let Some(peers) = peers(&self.profiles, id, similarity, min_movies, limit) else {
+            return error(404, "unknown id");
+        };
+        let names = &self.catalog.companies;
+        let peers: Vec<JsonValue> = peers
+            .into_iter()
+            .map(|x| {
+                object! {
+                    id: x.id,
+                    name: names.get(&x.id).map(|n| n.as_str()),
+                    score: x.score,
+                    genre: x.genre,
+                    budget: x.budget,
+                    cadence: x.cadence,
+                }
+            })
+            .collect();
+
+        (
+            200,
+            object! {
+                id: id,
+                name: names.get(&id).map(|n| n.as_str()),
+                similarity: similarity.name(),
+                peers: peers,
+            },
+        )
+    }
+
+    // /genres/combinations?decade=2000[&size=2][&limit=10][&min_movies=1]
+    fn genre_combinations(&self, query: &HashMap<String, String>) -> (u16, JsonValue) {
+        let decade = match query.get("decade").map(|y| y.parse::<i32>()) {
+            Some(Ok(decade)) => decade,
+            _ => return error(400, "decade is required and must be a number"),
+        };
+        let size = match query.get("size").map(|l| l.parse::<usize>()) {
+            Some(Ok(size)) if (1..=MAX_COMBINATION).contains(&size) => size,
+            None => 2,
+            _ => return error(400, "size must be 1, 2 or 3"),
+        };
+        let limit = match query.get("limit").map(|l| l.parse::<usize>()) {
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return error(400, "limit must be a number"),
+            None => DEFAULT_TOP_LIMIT,
+        };
+        let min_movies = match query.get("min_movies").map(|l| l.parse::<usize>()) {
+            Some(Ok(min_movies)) => min_movies,
+            Some(Err(_)) => return error(400, "min_movies must be a number"),
+            None => 1,
+        };
+
+        let Some(combinations) = self.combinations.get(&decade) else {
+            return error(404, "no releases that decade");
+        };
+        let names = &self.catalog.genres;
+        let results: Vec<JsonValue> = top_in_decade(combinations, size, min_movies, limit)
+            .into_iter()
+            .map(|(combination, stats)| {
+                let genres: Vec<JsonValue> = combination
+                    .iter()
+                    .map(|x| object! { id: *x, name: names.get(x).map(|n| n.as_str()) })
+                    .collect();
+                object! {
+                    genres: genres,
+                    movies: stats.movies,
+                    avg_budget: stats.avg_budget(),
+                    avg_revenue: stats.avg_revenue(),
+                    roi: stats.roi(),
+                }
+            })
+            .collect();
+
+        (
+            200,
+            object! {
+                decade: decade,
+                size: size,
+                results: results,
+            },
+        )
+    }
+
+    // /market-share?year=2010[&limit=10]
+    fn market_share(&self, query: &HashMap<String, String>) -> (u16, JsonValue) {
+        let year = match query.get("year").map(|y| y.parse::<i32>()) {
+            Some(Ok(year)) => year,
+            _ => return error(400, "year is required and must be a number"),
+        };
+        let limit = match query.get("limit").map(|l| l.parse::<usize>()) {
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return error(400, "limit must be a number"),
+            None => DEFAULT_TOP_LIMIT,
+        };
+
+        let Some(period) = market_share(&self.by_company)
+            .into_iter()
+            .find(|x| x.year == year)
+        else {
+            return error(404, "no releases that year");
+        };
+
+        let shares: Vec<JsonValue> = period
+            .shares
+            .iter()
+            .take(limit)
+            .map(|x| {
+                object! {
+                    id: x.id,
+                    name: self.catalog.companies.get(&x.id).map(|n| n.as_str()),
+                    revenue: x.revenue,
+                    releases: x.releases,
+                    revenue_share: x.revenue_share,
+                    release_share: x.release_share,
+                    shift: x.shift,
+                }
+            })
+            .collect();
+
+        (
+            200,
+            object! {
+                year: year,
+                revenue: period.revenue,
+                releases: period.releases,
+                hhi: period.hhi,
+                top_share: period.top_share,
+                shares: shares,
+            },
+        )
+    }
+
+    // /{companies,genres}/{id}/forecast[?model=linear|holt_winters][&series=revenue|releases]
+    //     [&periods=12][&holdout_years=1]
+    fn entity_forecast<T: ById + Totals>(
+        &self,
+        map: &BucketYearMap<T>,
+        id: i64,
+        query: &HashMap<String, String>,
+    ) -> (u16, JsonValue) {
+        let model = match query.get("model").map(|x| Model::from_name(x)) {
+            Some(Some(model)) => model,
+            Some(None) => return error(400, "model must be linear or holt_winters"),
+            None => Model::HoltWinters,
+        };
+        let series = match query.get("series").map(|x| Series::from_name(x)) {
+            Some(Some(series)) => series,
+            Some(None) => return error(400, "series must be revenue or releases"),
+            None => Series::Revenue,
+        };
+        let periods = match query.get("periods").map(|l| l.parse::<usize>()) {
+            Some(Ok(periods)) if periods <= MAX_PERIODS => periods,
+            Some(Ok(_)) => return error(400, &format!("periods must be at most {MAX_PERIODS}")),
+            Some(Err(_)) => return error(400, "periods must be a number"),
+            None => self.forecast_periods,
+        };
+        let holdout_years = match query.get("holdout_years").map(|l| l.parse::<usize>()) {
+            Some(Ok(holdout_years)) if holdout_years <= MAX_HOLDOUT_YEARS => holdout_years,
+            Some(Ok(_)) => {
+                return error(
+                    400,
+                    &format!("holdout_years must be at most {MAX_HOLDOUT_YEARS}"),
+                )
+            }
+            Some(Err(_)) => return error(400, "holdout_years must be a number"),
+            None => self.holdout_years,
+        };
+
+        let Some(monthly) = MonthlySeries::of(map, id, series) else {
+            return error(404, "unknown id");
+        };
+        let Some(forecast) = model.forecast(&monthly.values, periods) else {
+            return error(422, "not enough history for this model");
+        };
+
+        let forecast: Vec<JsonValue> = forecast
+            .into_iter()
+            .enumerate()
+            .map(|(offset, value)| {
+                let (year, month) = monthly.month_at(monthly.values.len() + offset);
+                object! { year: year, month: month, value: value }
+            })
+            .collect();
+        let backtest = backtest(&monthly.values, model, holdout_years).map(|x| {
+            object! {
+                holdout_years: holdout_years,
+                mae: x.mae,
+                rmse: x.rmse,
+                mape: x.mape,
+            }
+        });
+
+        (
+            200,
+            object! {
+                id: id,
+                model: model.name(),
+                series: series.name(),
+                forecast: forecast,
+                backtest: backtest,
+            },
+        )
+    }
+}
+
+pub fn run(api: &Api, port: u16) {
+    let server = Server::http(("127.0.0.1", port)).expect("Couldn't bind server...");
+    println!("listening on http://{}", server.server_addr());
+
+    for request in server.incoming_requests() {
+        respond(api, request);
+    }
+}
+
+fn respond(api: &Api, request: Request) {
+    let (status, body) = api.route(request.method(), request.url());
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(body.dump())
+        .with_status_code(status)
+        .with_header(header);
+
+    if let Err(e) = request.respond(response) {
+        println!("failed to respond: {e}");
+    }
+}
+
+fn entity_timeline<T: ById + Clone + Totals>(
+    map: &BucketYearMap<T>,
+    names: &Dictionary,
+    id: i64,
+) -> (u16, JsonValue) {
+    let years = timeline(map, id);
+    if years.is_empty() && !names.contains_key(&id) {
+        return error(404, "unknown id");
+    }
+
+    let timeline: Vec<JsonValue> = years
+        .iter()
+        .map(|(year, totals)| {
+            let mut entry = totals_json(totals.as_ref());
+            entry["year"] = (*year).into();
+            entry
+        })
+        .collect();
+
+    (
+        200,
+        object! {
+            id: id,
+            name: names.get(&id).map(|n| n.as_str()),
+            timeline: timeline,
+        },
+    )
+}
+
+fn entity_seasonality<T: ById + Totals>(
+    map: &BucketYearMap<T>,
+    market: &MonthTotals,
+    id: i64,
+) -> (u16, JsonValue) {
+    let Some(seasonality) = seasonality(map, id, market) else {
+        return error(404, "unknown id");
+    };
+
+    let months: Vec<JsonValue> = (0..12)
+        .map(|m| {
+            object! {
+                month: m + 1,
+                releases: seasonality.totals.releases[m],
+                revenue: seasonality.totals.revenue[m],
+                release_share: seasonality.release_share[m],
+                revenue_share: seasonality.revenue_share[m],
+                release_index: seasonality.release_index[m],
+                revenue_index: seasonality.revenue_index[m],
+            }
+        })
+        .collect();
+
+    (
+        200,
+        object! {
+            id: id,
+            best_month: seasonality.best_month,
+            worst_month: seasonality.worst_month,
+            months: months,
+        },
+    )
+}
+
+pub fn ranked<T: ById + Totals>(
+    entries: &[Rc<T>],
+    names: &Dictionary,
+    metric: Metric,
+) -> Vec<JsonValue> {
+    entries
+        .iter()
+        .map(|entry| {
+            let mut json = totals_json(entry.as_ref());
+            json["id"] = entry.id().into();
+            json["name"] = names.get(&entry.id()).map(|n| n.as_str()).into();
+            json["value"] = metric.value(entry.as_ref()).into();
+            json
+        })
+        .collect()
+}
+
+fn totals_json<T: Totals>(totals: &T) -> JsonValue {
+    object! {
+        movies: totals.movie_count(),
+        budget: totals.budget(),
+        profit: totals.profit(),
+        revenue: totals.revenue(),
+        avg_popularity: totals.avg_popularity(),
+        ratings: totals.ratings().count,
+        avg_rating: totals.ratings().mean(),
+        adjusted: totals.adjusted().map(|x| object! {
+            budget: x.budget,
+            profit: x.profit,
+            revenue: x.revenue,
+        }),
+        roi: totals.returns().roi(),
+        median_profit: totals.returns().median_profit(),
+        hit_rate: totals.returns().hit_rate(),
+        flops: totals.returns().flops,
+        distributions: totals.distributions().map(distributions_json),
+    }
+}
+
+fn distributions_json(distributions: &Distributions) -> JsonValue {
+    let mut json = JsonValue::new_object();
+    for (metric, sketch) in distributions.by_name() {
+        let histogram: Vec<JsonValue> = sketch
+            .histogram()
+            .into_iter()
+            .map(|(lower, upper, count)| object! { lower: lower, upper: upper, count: count })
+            .collect();
+        json[metric] = object! {
+            p50: sketch.quantile(0.5),
+            p90: sketch.quantile(0.9),
+            p99: sketch.quantile(0.99),
+            histogram: histogram,
+        };
+    }
+    json
+}
+
+fn error(status: u16, message: &str) -> (u16, JsonValue) {
+    (status, object! { error: message })
+}
+
+// no percent-decoding; every parameter we accept is a plain word or number.
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|x| !x.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (k.to_lowercase(), v.to_string())
+        })
+        .collect()
+}
+
+mod tests;
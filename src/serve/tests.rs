@@ -0,0 +1,289 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+    use crate::{
+        data::Movie,
+        query::forecast::{DEFAULT_HOLDOUT_YEARS, DEFAULT_PERIODS},
+    };
+    use chrono::NaiveDate;
+    use kstring::KString;
+    use std::{
+        collections::BTreeMap,
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+    };
+
+    fn movie(id: &str, year: i32, companies: &[i64], genres: &[i64], revenue: i64) -> Movie {
+        Movie {
+            id: KString::from_ref(id),
+            genres: genres.iter().copied().collect(),
+            production_companies: companies.iter().copied().collect(),
+            release_date: NaiveDate::from_ymd_opt(year, 6, 1).unwrap(),
+            budget: 10,
+            revenue,
+            profit: revenue - 10,
+            ..Default::default()
+        }
+    }
+
+    fn api() -> Api {
+        Api::new(
+            Catalog {
+                movies: vec![
+                    movie("1", 2010, &[7, 8], &[28], 100),
+                    movie("2", 2010, &[7], &[35], 50),
+                    movie("3", 2011, &[8], &[28], 300),
+                ],
+                genres: BTreeMap::from([
+                    (28, KString::from_static("Action")),
+                    (35, KString::from_static("Comedy")),
+                ]),
+                companies: BTreeMap::from([
+                    (7, KString::from_static("Pixar")),
+                    (8, KString::from_static("Disney")),
+                ]),
+                ..Default::default()
+            },
+            true,
+            &Allocation::Full,
+            DEFAULT_PERIODS,
+            DEFAULT_HOLDOUT_YEARS,
+        )
+    }
+
+    #[test]
+    fn company_timeline() {
+        let (status, body) = api().route(&Method::Get, "/companies/8/timeline");
+        assert_eq!(status, 200);
+        assert_eq!(body["name"], "Disney");
+        assert_eq!(body["timeline"].len(), 2);
+        assert_eq!(body["timeline"][0]["year"], 2010);
+        assert_eq!(body["timeline"][0]["revenue"], 100);
+        assert_eq!(body["timeline"][1]["year"], 2011);
+        assert_eq!(body["timeline"][1]["profit"], 290);
+        assert_eq!(
+            body["timeline"][0]["distributions"]["revenue"]["p50"],
+            100.0
+        );
+        assert_eq!(
+            body["timeline"][0]["distributions"]["revenue"]["histogram"][0]["count"],
+            1
+        );
+    }
+
+    #[test]
+    fn genre_timeline() {
+        let (status, body) = api().route(&Method::Get, "/genres/28/timeline");
+        assert_eq!(status, 200);
+        assert_eq!(body["name"], "Action");
+        assert_eq!(body["timeline"][0]["movies"], 1);
+        assert_eq!(body["timeline"][1]["revenue"], 300);
+    }
+
+    #[test]
+    fn top_by_metric() {
+        let api = api();
+        let (status, body) = api.route(&Method::Get, "/top?metric=profit&year=2010");
+        assert_eq!(status, 200);
+        assert_eq!(body["metric"], "profit");
+        assert_eq!(body["results"].len(), 2);
+        assert_eq!(body["results"][0]["name"], "Pixar");
+        assert_eq!(body["results"][0]["value"], 130.0);
+        assert_eq!(body["results"][1]["name"], "Disney");
+
+        let (_, body) = api.route(
+            &Method::Get,
+            "/top?metric=movies&year=2010&by=genres&limit=1",
+        );
+        assert_eq!(body["results"].len(), 1);
+
+        let (_, body) = api.route(&Method::Get, "/top?year=1900");
+        assert_eq!(body["results"].len(), 0);
+    }
+
+    #[test]
+    fn company_seasonality() {
+        let (status, body) = api().route(&Method::Get, "/companies/7/seasonality");
+        assert_eq!(status, 200);
+        assert_eq!(body["months"].len(), 12);
+        assert_eq!(body["months"][5]["releases"], 2);
+        assert_eq!(body["months"][5]["revenue_share"], 1.0);
+        assert_eq!(body["months"][5]["release_index"], 1.0);
+        assert!(body["months"][0]["release_index"].is_null());
+        assert_eq!(body["best_month"], 6);
+
+        let (status, _) = api().route(&Method::Get, "/genres/35/seasonality");
+        assert_eq!(status, 200);
+        assert_eq!(api().route(&Method::Get, "/genres/99/seasonality").0, 404);
+    }
+
+    #[test]
+    fn market_share_by_year() {
+        let api = api();
+        let (status, body) = api.route(&Method::Get, "/market-share?year=2010");
+        assert_eq!(status, 200);
+        assert_eq!(body["revenue"], 250);
+        assert_eq!(body["shares"][0]["name"], "Pixar");
+        assert_eq!(body["shares"][0]["revenue_share"], 0.6);
+        assert!(body["shares"][0]["shift"].is_null());
+
+        let (_, body) = api.route(&Method::Get, "/market-share?year=2011&limit=1");
+        assert_eq!(body["shares"].len(), 1);
+        assert_eq!(body["shares"][0]["shift"], 0.6);
+        assert_eq!(body["hhi"], 10000.0);
+
+        assert_eq!(api.route(&Method::Get, "/market-share?year=1900").0, 404);
+        assert_eq!(api.route(&Method::Get, "/market-share").0, 400);
+    }
+
+    #[test]
+    fn company_partners() {
+        let api = api();
+        let (status, body) = api.route(&Method::Get, "/companies/7/partners");
+        assert_eq!(status, 200);
+        assert_eq!(body["partners"].len(), 1);
+        assert_eq!(body["partners"][0]["name"], "Disney");
+        assert_eq!(body["partners"][0]["revenue"], 100);
+        assert_eq!(body["by_year"][0]["year"], 2010);
+        assert_eq!(body["by_year"][0]["partners"][0]["id"], 8);
+
+        assert_eq!(api.route(&Method::Get, "/companies/99/partners").0, 404);
+    }
+
+    #[test]
+    fn company_peers() {
+        let api = api();
+        let (status, body) = api.route(&Method::Get, "/companies/7/peers?min_movies=1");
+        assert_eq!(status, 200);
+        assert_eq!(body["similarity"], "cosine");
+        assert_eq!(body["peers"].len(), 1);
+        assert_eq!(body["peers"][0]["name"], "Disney");
+
+        let (_, body) = api.route(&Method::Get, "/companies/7/peers");
+        assert_eq!(body["peers"].len(), 0);
+        assert_eq!(
+            api.route(&Method::Get, "/companies/7/peers?similarity=vibes")
+                .0,
+            400
+        );
+        assert_eq!(api.route(&Method::Get, "/companies/99/peers").0, 404);
+    }
+
+    #[test]
+    fn genre_combinations_by_decade() {
+        let api = api();
+        let (status, body) = api.route(&Method::Get, "/genres/combinations?decade=2010&size=1");
+        assert_eq!(status, 200);
+        assert_eq!(body["results"].len(), 2);
+        assert_eq!(body["results"][0]["genres"][0]["name"], "Action");
+        assert_eq!(body["results"][0]["movies"], 2);
+        assert_eq!(body["results"][0]["avg_revenue"], 200.0);
+
+        let (_, body) = api.route(&Method::Get, "/genres/combinations?decade=2010");
+        assert_eq!(body["results"].len(), 0);
+        assert_eq!(
+            api.route(&Method::Get, "/genres/combinations?decade=2010&size=4")
+                .0,
+            400
+        );
+        assert_eq!(api.route(&Method::Get, "/genres/combinations").0, 400);
+        assert_eq!(
+            api.route(&Method::Get, "/genres/combinations?decade=1900")
+                .0,
+            404
+        );
+    }
+
+    #[test]
+    fn company_forecast() {
+        let api = api();
+        let (status, body) = api.route(
+            &Method::Get,
+            "/companies/8/forecast?model=linear&periods=2&holdout_years=0",
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["model"], "linear");
+        assert_eq!(body["series"], "revenue");
+        assert_eq!(body["forecast"].len(), 2);
+        assert_eq!(body["forecast"][0]["year"], 2011);
+        assert_eq!(body["forecast"][0]["month"], 7);
+        assert!(body["backtest"].is_null());
+
+        // a year and a month of history isn't enough for the seasonal model.
+        assert_eq!(api.route(&Method::Get, "/companies/8/forecast").0, 422);
+        assert_eq!(
+            api.route(
+                &Method::Get,
+                "/genres/28/forecast?model=linear&series=releases"
+            )
+            .0,
+            200
+        );
+        assert_eq!(
+            api.route(&Method::Get, "/companies/8/forecast?model=magic")
+                .0,
+            400
+        );
+        assert_eq!(api.route(&Method::Get, "/companies/99/forecast").0, 404);
+
+        // without periods in the request, the configured default applies.
+        let mut configured = self::api();
+        configured.forecast_periods = 3;
+        let (_, body) = configured.route(&Method::Get, "/companies/8/forecast?model=linear");
+        assert_eq!(body["forecast"].len(), 3);
+        assert_eq!(
+            api.route(&Method::Get, "/companies/8/forecast?periods=10000000000")
+                .0,
+            400
+        );
+        assert_eq!(
+            api.route(
+                &Method::Get,
+                "/companies/8/forecast?model=linear&holdout_years=18446744073709551615"
+            )
+            .0,
+            400
+        );
+    }
+
+    #[test]
+    fn bad_requests() {
+        let api = api();
+        assert_eq!(api.route(&Method::Get, "/nope").0, 404);
+        assert_eq!(api.route(&Method::Get, "/companies/999/timeline").0, 404);
+        assert_eq!(api.route(&Method::Get, "/companies/abc/timeline").0, 400);
+        assert_eq!(api.route(&Method::Get, "/top?metric=profit").0, 400);
+        assert_eq!(
+            api.route(&Method::Get, "/top?metric=vibes&year=2010").0,
+            400
+        );
+        assert_eq!(api.route(&Method::Post, "/top?year=2010").0, 405);
+    }
+
+    #[test]
+    fn serves_over_http() {
+        let api = api();
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET /companies/7/timeline HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+
+        respond(&api, server.recv().unwrap());
+
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        let body = json::parse(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["name"], "Pixar");
+        assert_eq!(body["timeline"][0]["movies"], 2);
+    }
+}
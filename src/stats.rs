@@ -0,0 +1,159 @@
+use chrono::NaiveDate;
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Read,
+};
+
+use crate::{
+    data::{movie_reader, read_headers},
+    parsing,
+};
+
+const PERCENTILES: [f64; 5] = [0.25, 0.5, 0.75, 0.9, 0.99];
+
+// column-level profile of a movies_metadata.csv, over every row rather than only the ones
+// ingest keeps.
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub rows: u64,
+    // per header, in file order.
+    pub columns: Vec<String>,
+    pub blanks: Vec<u64>,
+    // rows that failed to deserialize, keyed by the column serde complained about.
+    pub failures: BTreeMap<String, u64>,
+    pub statuses: BTreeMap<String, u64>,
+    pub budget: Vec<f64>,
+    pub revenue: Vec<f64>,
+    pub popularity: Vec<f64>,
+    pub first_release: Option<NaiveDate>,
+    pub last_release: Option<NaiveDate>,
+    pub genres: HashSet<i64>,
+    pub companies: HashSet<i64>,
+}
+
+pub fn profile(reader: impl Read) -> Profile {
+    let mut reader = movie_reader(reader);
+    // aliases still apply, but a missing column shouldn't stop the profile.
+    let (headers, _) = parsing::normalize_headers(&read_headers(&mut reader));
+
+    let mut profile = Profile {
+        columns: headers.iter().map(String::from).collect(),
+        blanks: vec![0; headers.len()],
+        ..Default::default()
+    };
+
+    for record in reader.records().flatten() {
+        profile.rows += 1;
+        for (i, value) in record.iter().enumerate().take(headers.len()) {
+            if value.trim().is_empty() {
+                profile.blanks[i] += 1;
+            }
+        }
+
+        let raw = match parsing::from_record(&record, &headers) {
+            Ok(raw) => raw,
+            Err(e) => {
+                *profile
+                    .failures
+                    .entry(failed_column(&e, &headers))
+                    .or_default() += 1;
+                continue;
+            }
+        };
+
+        *profile
+            .statuses
+            .entry(raw.status().to_string())
+            .or_default() += 1;
+        profile.budget.push(raw.budget() as f64);
+        profile.revenue.push(raw.revenue() as f64);
+        if let Some(popularity) = raw.popularity() {
+            profile.popularity.push(popularity as f64);
+        }
+
+        let date = raw.release_date();
+        profile.first_release = Some(profile.first_release.map_or(date, |x| x.min(date)));
+        profile.last_release = Some(profile.last_release.map_or(date, |x| x.max(date)));
+        profile.genres.extend(raw.genre_names().into_keys());
+        profile.companies.extend(raw.company_names().into_keys());
+    }
+
+    profile.budget.sort_by(f64::total_cmp);
+    profile.revenue.sort_by(f64::total_cmp);
+    profile.popularity.sort_by(f64::total_cmp);
+    profile
+}
+
+// nearest-rank percentile of already sorted values.
+pub fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+pub fn print_profile(profile: &Profile) {
+    println!("rows: {}", profile.rows);
+
+    println!("blank cells:");
+    for (column, blanks) in profile.columns.iter().zip(&profile.blanks) {
+        println!("  {column:<24} {blanks:>8}");
+    }
+
+    println!("rows failing to parse:");
+    for (column, count) in &profile.failures {
+        println!("  {column:<24} {count:>8}");
+    }
+
+    println!("statuses:");
+    for (status, count) in profile.statuses.iter().sorted_by_key(|(_, x)| **x).rev() {
+        let status = if status.is_empty() { "(blank)" } else { status };
+        println!("  {status:<24} {count:>8}");
+    }
+
+    print_distribution("budget", &profile.budget);
+    print_distribution("revenue", &profile.revenue);
+    print_distribution("popularity", &profile.popularity);
+
+    if let (Some(first), Some(last)) = (profile.first_release, profile.last_release) {
+        println!("release dates: {first} to {last}");
+    }
+    println!("distinct genres: {}", profile.genres.len());
+    println!("distinct companies: {}", profile.companies.len());
+}
+
+fn print_distribution(name: &str, sorted: &[f64]) {
+    let (Some(min), Some(max)) = (sorted.first(), sorted.last()) else {
+        println!("{name}: no values");
+        return;
+    };
+    let percentiles = PERCENTILES
+        .iter()
+        .map(|p| {
+            format!(
+                "p{}: {:.2}",
+                (p * 100.0) as u32,
+                percentile(sorted, *p).unwrap()
+            )
+        })
+        .join(" ");
+    println!(
+        "{name}: n: {} min: {min:.2} {percentiles} max: {max:.2}",
+        sorted.len()
+    );
+}
+
+fn failed_column(e: &csv::Error, headers: &csv::StringRecord) -> String {
+    match e.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err
+            .field()
+            .and_then(|i| headers.get(i as usize))
+            .map(String::from)
+            .unwrap_or_else(|| "(row)".to_string()),
+        _ => "(row)".to_string(),
+    }
+}
+
+mod tests;
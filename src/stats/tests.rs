@@ -0,0 +1,35 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn percentiles() {
+        let values: Vec<f64> = (1..=100).map(|x| x as f64).collect();
+        assert_eq!(percentile(&values, 0.5), Some(50.0));
+        assert_eq!(percentile(&values, 0.99), Some(99.0));
+        assert_eq!(percentile(&values, 0.0), Some(1.0));
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+
+    #[test]
+    fn profiles_every_row() {
+        let file = "id,genres,production_companies,release_date,budget,revenue,popularity,status\n\
+             1,\"[{'id': 18, 'name': 'Drama'}]\",\"[{'id': 3, 'name': 'Pixar'}]\",1995-10-30,100,200,1.5,Released\n\
+             2,[],\"[{'id': 4, 'name': 'Fox'}]\",2001-01-01,0,0,,Rumored\n\
+             3,[],[],1999-01-01,abc,0,,Released\n";
+
+        let profile = profile(file.as_bytes());
+        assert_eq!(profile.rows, 3);
+        assert_eq!(profile.blanks[6], 2);
+        assert_eq!(profile.failures.get("budget"), Some(&1));
+        assert_eq!(profile.statuses.get("Released"), Some(&1));
+        assert_eq!(profile.statuses.get("Rumored"), Some(&1));
+        assert_eq!(profile.budget, vec![0.0, 100.0]);
+        assert_eq!(profile.popularity, vec![1.5]);
+        assert_eq!(profile.first_release, NaiveDate::from_ymd_opt(1995, 10, 30));
+        assert_eq!(profile.last_release, NaiveDate::from_ymd_opt(2001, 1, 1));
+        assert_eq!(profile.genres.len(), 1);
+        assert_eq!(profile.companies.len(), 2);
+    }
+}
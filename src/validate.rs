@@ -0,0 +1,71 @@
+use std::io::Read;
+
+use crate::{
+    data::{movie_reader, read_headers},
+    parsing::{self, Schema, SchemaError},
+};
+
+// how many rows past the header `validate` tries to deserialize.
+pub const SAMPLE_ROWS: usize = 100;
+// failures listed individually; the rest are only counted.
+const SHOWN_FAILURES: usize = 10;
+
+#[derive(Debug)]
+pub struct Validation {
+    pub schema: Schema,
+    pub sampled: usize,
+    // (line, error) per row that didn't deserialize.
+    pub failures: Vec<(Option<u64>, String)>,
+}
+
+// the header check plus a parse of the first `sample` rows; nothing is ingested.
+pub fn validate(reader: impl Read, sample: usize) -> Result<Validation, SchemaError> {
+    let mut reader = movie_reader(reader);
+    let schema = parsing::check_schema(&read_headers(&mut reader))?;
+
+    let mut sampled = 0;
+    let mut failures = vec![];
+    for record in reader.records().take(sample) {
+        sampled += 1;
+        match record {
+            Ok(record) => {
+                if let Err(e) = parsing::from_record(&record, &schema.headers) {
+                    let line = record.position().map(|x| x.line());
+                    failures.push((line, e.to_string()));
+                }
+            }
+            Err(e) => failures.push((e.position().map(|x| x.line()), e.to_string())),
+        }
+    }
+
+    Ok(Validation {
+        schema,
+        sampled,
+        failures,
+    })
+}
+
+pub fn print_validation(validation: &Validation) {
+    println!("header ok");
+    for (alias, column) in &validation.schema.aliased {
+        println!("  reading {alias} as {column}");
+    }
+    if !validation.schema.unknown.is_empty() {
+        println!(
+            "  unknown columns: {}",
+            validation.schema.unknown.join(", ")
+        );
+    }
+
+    println!(
+        "sample: {} of {} rows parsed",
+        validation.sampled - validation.failures.len(),
+        validation.sampled
+    );
+    for (line, e) in validation.failures.iter().take(SHOWN_FAILURES) {
+        let line = line.map(|x| x.to_string()).unwrap_or_default();
+        println!("  line {line}: {e}");
+    }
+}
+
+mod tests;
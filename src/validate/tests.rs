@@ -0,0 +1,34 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn samples_rows_after_the_header() {
+        let file = "id,genres,production_companies,release_date,budget,revenue,avg_popularity,status,homepage\n\
+             1,[],[],1995-10-30,100,200,1.5,Released,\n\
+             2,[],[],1995-10-30,abc,200,1.5,Released,\n\
+             3,[],[],1995-10-30,100,200,1.5,Released,\n";
+
+        let validation = validate(file.as_bytes(), 2).unwrap();
+        assert_eq!(validation.sampled, 2);
+        assert_eq!(validation.failures.len(), 1);
+        assert_eq!(validation.failures[0].0, Some(3));
+        assert_eq!(
+            validation.schema.aliased,
+            vec![("avg_popularity".to_string(), "popularity".to_string())]
+        );
+        assert!(validation.schema.unknown.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_renamed_column() {
+        let file = "id,genre,release_date\n";
+        let e = validate(file.as_bytes(), SAMPLE_ROWS).unwrap_err();
+        assert!(e.missing.contains(&"genres".to_string()));
+        assert_eq!(e.unknown, vec!["genre".to_string()]);
+        assert!(e
+            .to_string()
+            .starts_with("missing columns: genres, production_companies"));
+    }
+}